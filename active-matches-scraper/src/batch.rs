@@ -0,0 +1,171 @@
+use crate::ActiveMatch;
+use ingest_common::file_name::IngestFileName;
+use ingest_common::snapshot_delta::{self, Line};
+use std::collections::HashSet;
+use std::io::{BufRead, BufReader};
+use std::path::Path;
+use std::sync::LazyLock;
+use std::time::{Duration, SystemTime};
+
+static MATCHES_PER_FILE: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("MATCHES_PER_FILE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1000)
+});
+
+/// Maximum uncompressed size of a batch in bytes
+static MAX_FILE_BYTES: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("MAX_FILE_BYTES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(256 * 1024 * 1024)
+});
+
+/// Maximum time between writing the first match of a batch and sealing it
+static MAX_FILE_AGE_S: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("MAX_FILE_AGE_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(900)
+});
+
+/// Whether new batches are delta-encoded, disable to keep writing full snapshots until every
+/// consumer reads delta-encoded batches.
+static DELTA_ENCODE_BATCHES: LazyLock<bool> = LazyLock::new(|| {
+    std::env::var("DELTA_ENCODE_BATCHES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(true)
+});
+
+/// The finished matches written to the current batch file.
+#[derive(Debug, Default)]
+pub struct Batch {
+    pub matches: usize,
+    pub bytes: u64,
+    delta_encoded: bool,
+    match_ids: HashSet<u32>,
+    first_scraped_at: Option<u32>,
+    last_scraped_at: Option<u32>,
+    started_at: Option<SystemTime>,
+}
+
+impl Batch {
+    pub fn new() -> Self {
+        Self {
+            delta_encoded: *DELTA_ENCODE_BATCHES,
+            ..Default::default()
+        }
+    }
+
+    /// Restores the batch from its file after a restart, keeping the format of its lines.
+    pub fn load(path: &Path) -> Self {
+        let mut batch = Self::new();
+        let Ok(file) = std::fs::File::open(path) else {
+            return batch;
+        };
+        for line in BufReader::new(file).lines().map_while(Result::ok) {
+            let parsed: serde_json::Result<Line<ActiveMatch>> = serde_json::from_str(&line);
+            let (delta_encoded, snapshots) = match parsed {
+                Ok(line) => (
+                    matches!(line, Line::Delta(_)),
+                    line.into_snapshots().unwrap_or_default(),
+                ),
+                Err(_) => (batch.delta_encoded, vec![]),
+            };
+            // The lines of a batch file all have the format of the first one
+            if batch.matches == 0 {
+                batch.delta_encoded = delta_encoded;
+            }
+            batch.add(&snapshots, line.len() + 1);
+        }
+        // The age is measured from the creation of the file, if the file system tracks it
+        batch.started_at = std::fs::metadata(path)
+            .and_then(|m| m.created().or_else(|_| m.modified()))
+            .ok()
+            .filter(|_| batch.matches > 0);
+        batch
+    }
+
+    /// Serializes the snapshots of a finished match into a line of the batch file.
+    pub fn line(&self, snapshots: &[ActiveMatch]) -> serde_json::Result<String> {
+        if self.delta_encoded {
+            serde_json::to_string(&snapshot_delta::encode(snapshots)?)
+        } else {
+            serde_json::to_string(snapshots)
+        }
+    }
+
+    /// Adds the snapshots of a finished match, which took `bytes` in the batch file.
+    pub fn add(&mut self, snapshots: &[ActiveMatch], bytes: usize) {
+        self.matches += 1;
+        self.bytes += bytes as u64;
+        self.match_ids.extend(snapshots.first().map(|s| s.match_id));
+        for snapshot in snapshots {
+            self.first_scraped_at = Some(
+                self.first_scraped_at
+                    .map_or(snapshot.scraped_at, |s| s.min(snapshot.scraped_at)),
+            );
+            self.last_scraped_at = Some(
+                self.last_scraped_at
+                    .map_or(snapshot.scraped_at, |s| s.max(snapshot.scraped_at)),
+            );
+        }
+        self.started_at.get_or_insert_with(SystemTime::now);
+    }
+
+    /// Whether the match was already written to the batch file.
+    pub fn contains(&self, match_id: u32) -> bool {
+        self.match_ids.contains(&match_id)
+    }
+
+    /// Batches are sealed on whichever of match count, size or age is reached first.
+    pub fn is_full(&self) -> bool {
+        let max_age = Duration::from_secs(*MAX_FILE_AGE_S);
+        self.matches >= *MATCHES_PER_FILE
+            || self.bytes >= *MAX_FILE_BYTES
+            || self
+                .started_at
+                .is_some_and(|s| s.elapsed().unwrap_or_default() >= max_age)
+    }
+
+    /// The name covers the time window of all snapshots in the batch.
+    pub fn file_name(&self) -> IngestFileName {
+        let now = crate::scraped_at();
+        IngestFileName::new_active_matches(
+            self.first_scraped_at.unwrap_or(now).into(),
+            self.last_scraped_at.unwrap_or(now).into(),
+            self.delta_encoded,
+            Some("zst"),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ingest_common::testing;
+    use std::io::Write;
+
+    #[test]
+    fn load_restores_the_written_matches() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("active-matches.jsonl");
+        let mut file = std::fs::File::create(&path).unwrap();
+        for (match_id, scraped_at) in [(1, 100), (2, 200)] {
+            let snapshot = testing::active_match(match_id, scraped_at);
+            writeln!(file, "{}", serde_json::json!([snapshot])).unwrap();
+        }
+
+        let batch = Batch::load(&path);
+        assert_eq!(batch.matches, 2);
+        assert!(batch.contains(1));
+        assert!(batch.contains(2));
+        assert!(!batch.contains(3));
+        // The format of the file is kept, whatever new batches use
+        assert_eq!(batch.file_name().to_string(), "100-200.amjsonl.zst");
+        let line = batch.line(&[]).unwrap();
+        assert_eq!(line, "[]");
+    }
+}
@@ -1,23 +1,23 @@
 use async_compression::tokio::write::ZstdEncoder;
+use ingest_common::object_store::Store;
+use ingest_common::queue::Broker;
 use log::{debug, error, info};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::io::BufRead;
+use std::collections::HashSet;
 use std::sync::LazyLock;
 use std::time::{Duration, SystemTime};
 use tokio::fs::OpenOptions;
 use tokio::io;
 use tokio::io::AsyncWriteExt;
 use tokio::time::sleep;
-mod rmq;
-mod s3;
+mod batch;
+mod queue;
+mod spool;
+mod state;
 
 static REQUEST_INTERVAL: LazyLock<u64> =
     LazyLock::new(|| std::env::var("REQUEST_INTERVAL").ok().and_then(|s| s.parse().ok()).unwrap_or(21));
 
-static MATCHES_PER_FILE: LazyLock<usize> =
-    LazyLock::new(|| std::env::var("MATCHES_PER_FILE").ok().and_then(|s| s.parse().ok()).unwrap_or(1000));
-
 static CACHE_FOLDER: LazyLock<String> =
     LazyLock::new(|| std::env::var("CACHE_FOLDER").unwrap_or("./tmp".to_string()));
 
@@ -26,20 +26,42 @@ async fn main() {
     env_logger::init();
 
     let interval = Duration::from_secs(*REQUEST_INTERVAL);
-    let mut active_matches: HashMap<u32, Vec<ActiveMatch>> = HashMap::new();
 
     let parent_dir = std::path::Path::new(&*CACHE_FOLDER);
     if !parent_dir.exists() {
         std::fs::create_dir_all(parent_dir).expect("Error creating cache folder");
     }
-    let mut match_count = std::fs::File::open(format!("{}/active-matches.jsonl", *CACHE_FOLDER))
-        .map(std::io::BufReader::new)
-        .map(|i| i.lines().count())
-        .unwrap_or(0);
+    let (mut snapshot_log, mut active_matches) =
+        state::SnapshotLog::open(parent_dir.join("in-flight.jsonl"))
+            .expect("Error opening snapshot log");
+    // Configured upfront, so a misconfiguration fails the scraper instead of the uploader task
+    let store = Store::from_env().expect("Error configuring object store");
+    let broker = Broker::from_env("%2f").expect("Error configuring queue");
+    let spool = spool::Spool::open(parent_dir.join("spool"), store, broker)
+        .await
+        .expect("Error opening spool");
+    let uploader = tokio::spawn(spool.clone().run_uploader());
+
+    // Matches restored from the snapshot log, which might have finished while the scraper was down
+    let mut restored_ids: HashSet<u32> = active_matches.keys().copied().collect();
+    let mut batch = batch::Batch::load(&parent_dir.join("active-matches.jsonl"));
+    // Matches written to the batch before a crash, which were not marked finished in the log yet
+    let written_ids: Vec<u32> =
+        restored_ids.iter().copied().filter(|id| batch.contains(*id)).collect();
+    if !written_ids.is_empty() {
+        info!("Skipping {} restored matches already in the batch", written_ids.len());
+        active_matches.retain(|k, _| !written_ids.contains(k));
+        restored_ids.retain(|k| !written_ids.contains(k));
+        snapshot_log.mark_finished(written_ids).expect("Error writing snapshot log");
+    }
 
     let mut file_writer;
     loop {
-        while match_count < *MATCHES_PER_FILE {
+        while !batch.is_full() {
+            if uploader.is_finished() {
+                // Without the uploader no batch would leave the spool anymore
+                panic!("Batch uploader stopped: {:?}", uploader.await);
+            }
             let start = std::time::Instant::now();
             file_writer = match OpenOptions::new()
                 .write(true)
@@ -69,15 +91,25 @@ async fn main() {
             let new_active_matches_ids: Vec<u32> =
                 new_active_matches.iter().map(|am| am.match_id).collect();
 
+            snapshot_log
+                .append_snapshots(&new_active_matches)
+                .expect("Error writing snapshot log");
             for am in new_active_matches {
                 active_matches.entry(am.match_id).or_default().push(am);
             }
 
-            for (match_id, matches) in active_matches.iter() {
+            let finished_at = scraped_at();
+            let mut finished_ids = vec![];
+            for (match_id, matches) in active_matches.iter_mut() {
                 if new_active_matches_ids.contains(match_id) || matches.is_empty() {
                     continue;
                 }
-                let match_string = match serde_json::to_string(matches) {
+                if restored_ids.contains(match_id) {
+                    for am in matches.iter_mut() {
+                        am.history_incomplete = true;
+                    }
+                }
+                let match_string = match batch.line(matches) {
                     Ok(s) => s,
                     Err(e) => {
                         error!("Error serializing match {}: {:?}", match_id, e);
@@ -93,41 +125,53 @@ async fn main() {
                     .await
                     .expect("Error writing to file");
                 file_writer.flush().await.expect("Error flushing file");
-                match_count += 1;
+                finished_ids.push(*match_id);
+                batch.add(matches, match_string.len() + 1);
+                if let Some(final_snapshot) = matches.last() {
+                    // The time a restored match finished at is unknown
+                    let finished_at =
+                        Some(finished_at).filter(|_| !restored_ids.contains(match_id));
+                    let event = queue::MatchEvent::finished(final_snapshot, finished_at);
+                    if let Err(e) = spool.add_event(&event).await {
+                        error!("Error spooling event for match {}: {:?}", match_id, e);
+                    }
+                }
             }
+            file_writer.sync_data().await.expect("Error syncing file");
 
             active_matches.retain(|k, _| new_active_matches_ids.contains(k));
-
-            info!("Currently having {} finished matches", match_count);
+            snapshot_log
+                .mark_finished(finished_ids)
+                .expect("Error writing snapshot log");
+            snapshot_log
+                .compact_if_needed(&active_matches)
+                .expect("Error compacting snapshot log");
+            restored_ids.clear();
+
+            info!(
+                "Currently having {} finished matches ({} bytes)",
+                batch.matches, batch.bytes
+            );
 
             if start.elapsed() < interval - Duration::from_secs(2) {
                 sleep(interval - start.elapsed()).await;
             }
         }
 
-        info!("Uploading active matches to S3");
-        match compress_temp_file().await {
-            Ok(compressed_bytes) => {
-                let s3_path = &format!(
-                    "/ingest/active-matches/{}.amjsonl.zst",
-                    SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap()
-                        .as_secs()
-                );
-                s3::upload_to_s3(&compressed_bytes, s3_path)
-                    .await
-                    .expect("Error uploading to S3");
-                rmq::add_to_queue(s3_path)
-                    .await
-                    .expect("Error adding to RMQ");
-            }
-            Err(e) => {
-                error!("Error compressing file: {:?}", e);
-            }
+        info!("Sealing active matches batch");
+        let file_name = batch.file_name();
+        let sealed = match compress_temp_file().await {
+            Ok(compressed_bytes) => spool.seal(&file_name, &compressed_bytes).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = sealed {
+            // The batch file is kept, so sealing is retried
+            error!("Error sealing batch, retrying in {:?}: {:?}", interval, e);
+            sleep(interval).await;
+            continue;
         }
 
-        match_count = 0;
+        batch = batch::Batch::new();
         std::fs::remove_file(format!("{}/active-matches.jsonl", *CACHE_FOLDER))
             .expect("Error removing file");
     }
@@ -161,6 +205,9 @@ struct ActiveMatch {
     game_mode: u8,
     match_score: u32,
     region_mode: u8,
+    /// Set if snapshots are missing, because the match finished while the scraper was down
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    history_incomplete: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize)]
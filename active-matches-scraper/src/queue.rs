@@ -0,0 +1,56 @@
+use crate::ActiveMatch;
+use ingest_common::queue::{Headers, Queue, QueueError};
+use log::{debug, info};
+use serde::Serialize;
+
+const QUEUE: &str = "db_ingest_queue";
+
+/// Fanout exchange of the [`MatchEvent`]s, consumers bind their own queue to it. Events are
+/// delivered at least once, e.g. again if the scraper crashes before the match is marked finished.
+const EVENTS_EXCHANGE: &str = "match_events";
+
+pub async fn add_to_queue(broker: &impl Queue, body: &str) -> Result<(), QueueError> {
+    info!("Sending message {} to queue: {}", body, QUEUE);
+    broker
+        .publish(QUEUE, body.as_bytes(), &Headers::new())
+        .await
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MatchEvent<'a> {
+    /// The match dropped out of the active matches
+    MatchFinished {
+        match_id: u32,
+        lobby_id: u64,
+        /// Unset if the match finished while the scraper was down, its final snapshot is marked
+        /// with `history_incomplete` then
+        finished_at: Option<u32>,
+        final_snapshot: &'a ActiveMatch,
+    },
+}
+
+impl<'a> MatchEvent<'a> {
+    pub fn finished(final_snapshot: &'a ActiveMatch, finished_at: Option<u32>) -> Self {
+        Self::MatchFinished {
+            match_id: final_snapshot.match_id,
+            lobby_id: final_snapshot.lobby_id,
+            finished_at,
+            final_snapshot,
+        }
+    }
+
+    pub fn match_id(&self) -> u32 {
+        match self {
+            Self::MatchFinished { match_id, .. } => *match_id,
+        }
+    }
+}
+
+/// Publishes a serialized [`MatchEvent`].
+pub async fn publish_event(broker: &impl Queue, event: &[u8]) -> Result<(), QueueError> {
+    debug!("Publishing event to exchange: {}", EVENTS_EXCHANGE);
+    broker
+        .publish_to_exchange(EVENTS_EXCHANGE, event, &Headers::new())
+        .await
+}
@@ -0,0 +1,245 @@
+use crate::queue;
+use crate::queue::MatchEvent;
+use ingest_common::file_name::IngestFileName;
+use ingest_common::object_store::{ObjectStore, ObjectStoreError};
+use ingest_common::queue::{Queue, QueueError};
+use log::{error, info, warn};
+use std::fmt::Display;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+use tokio::sync::Notify;
+
+const MIN_BACKOFF: Duration = Duration::from_secs(1);
+const MAX_BACKOFF: Duration = Duration::from_secs(300);
+
+#[derive(Debug)]
+enum UploadError {
+    Io(io::Error),
+    Storage(ObjectStoreError),
+    Queue(QueueError),
+}
+
+impl Display for UploadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            Self::Storage(e) => write!(f, "{}", e),
+            Self::Queue(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+/// Events are spooled in this subdirectory, and published before the batches
+const EVENTS_DIR: &str = "events";
+
+/// Directory of sealed, compressed batches waiting for their upload. A batch is only removed
+/// once it is uploaded and queued for ingestion, so no batch is lost if either fails or the
+/// scraper restarts. Match events are spooled the same way until they are published.
+pub struct Spool<S, Q> {
+    dir: PathBuf,
+    store: S,
+    broker: Q,
+    notify: Notify,
+}
+
+impl<S: ObjectStore, Q: Queue> Spool<S, Q> {
+    pub async fn open(dir: PathBuf, store: S, broker: Q) -> io::Result<Arc<Self>> {
+        for dir in [dir.clone(), dir.join(EVENTS_DIR)] {
+            fs::create_dir_all(&dir).await?;
+            // Temporary files of batches or events that were not written completely
+            let mut entries = fs::read_dir(&dir).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                if entry.file_name().to_string_lossy().starts_with('.') {
+                    fs::remove_file(entry.path()).await?;
+                }
+            }
+        }
+        Ok(Arc::new(Self {
+            dir,
+            store,
+            broker,
+            notify: Notify::new(),
+        }))
+    }
+
+    /// Durably stores the batch and wakes up the uploader.
+    pub async fn seal(&self, file_name: &IngestFileName, data: &[u8]) -> io::Result<()> {
+        write_durably(&self.dir, &file_name.to_string(), data).await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    /// Durably stores the event and wakes up the uploader, which publishes it.
+    pub async fn add_event(&self, event: &MatchEvent<'_>) -> io::Result<()> {
+        let data = serde_json::to_vec(event)?;
+        // Events are published in the order they were spooled in
+        let file_name = format!("{:010}-{}.json", crate::scraped_at(), event.match_id());
+        write_durably(&self.dir.join(EVENTS_DIR), &file_name, &data).await?;
+        self.notify.notify_one();
+        Ok(())
+    }
+
+    async fn pending(&self, dir: &Path) -> io::Result<Vec<PathBuf>> {
+        let mut pending = vec![];
+        let mut entries = fs::read_dir(dir).await?;
+        while let Some(entry) = entries.next_entry().await? {
+            let file_name = entry.file_name().to_string_lossy().to_string();
+            if !file_name.starts_with('.') && entry.file_type().await?.is_file() {
+                pending.push(entry.path());
+            }
+        }
+        // Upload the oldest batches first
+        pending.sort();
+        Ok(pending)
+    }
+
+    async fn upload(&self, path: &Path) -> Result<(), UploadError> {
+        let data = fs::read(path).await.map_err(UploadError::Io)?;
+        let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+        let object_path = format!("/ingest/active-matches/{}", file_name);
+        self.store
+            .put(&object_path, &mut &*data)
+            .await
+            .map_err(UploadError::Storage)?;
+        queue::add_to_queue(&self.broker, &object_path)
+            .await
+            .map_err(UploadError::Queue)?;
+        fs::remove_file(path).await.map_err(UploadError::Io)
+    }
+
+    async fn publish(&self, path: &Path) -> Result<(), UploadError> {
+        let data = fs::read(path).await.map_err(UploadError::Io)?;
+        queue::publish_event(&self.broker, &data)
+            .await
+            .map_err(UploadError::Queue)?;
+        fs::remove_file(path).await.map_err(UploadError::Io)
+    }
+
+    /// Publishes pending events and uploads pending batches, including the ones left over from
+    /// before a restart, retrying with exponential backoff until they succeed. Never returns,
+    /// unless it panics.
+    pub async fn run_uploader(self: Arc<Self>) {
+        let mut backoff = MIN_BACKOFF;
+        loop {
+            let pending = match self.pending(&self.dir.join(EVENTS_DIR)).await {
+                Ok(events) => self
+                    .pending(&self.dir)
+                    .await
+                    .map(|batches| (events, batches)),
+                Err(e) => Err(e),
+            };
+            let (events, batches) = match pending {
+                Ok(pending) => pending,
+                Err(e) => {
+                    error!("Error listing spool: {:?}", e);
+                    tokio::time::sleep(backoff).await;
+                    continue;
+                }
+            };
+            let mut failed = false;
+            'upload: {
+                for path in events {
+                    if let Err(e) = self.publish(&path).await {
+                        warn!(
+                            "Error publishing event {:?}, retrying in {:?}: {}",
+                            path, backoff, e
+                        );
+                        failed = true;
+                        break 'upload;
+                    }
+                }
+                for path in batches {
+                    match self.upload(&path).await {
+                        Ok(_) => info!("Uploaded batch {:?}", path),
+                        Err(e) => {
+                            warn!(
+                                "Error uploading batch {:?}, retrying in {:?}: {}",
+                                path, backoff, e
+                            );
+                            failed = true;
+                            break 'upload;
+                        }
+                    }
+                }
+            }
+            if failed {
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(MAX_BACKOFF);
+            } else {
+                backoff = MIN_BACKOFF;
+                self.notify.notified().await;
+            }
+        }
+    }
+}
+
+/// Writes the file to a temporary file first, so it is never read partially written.
+async fn write_durably(dir: &Path, file_name: &str, data: &[u8]) -> io::Result<()> {
+    let temp_path = dir.join(format!(".{}", file_name));
+    let mut file = fs::File::create(&temp_path).await?;
+    file.write_all(data).await?;
+    file.sync_all().await?;
+    fs::rename(&temp_path, dir.join(file_name)).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ActiveMatch;
+    use ingest_common::object_store::LocalStore;
+    use ingest_common::queue::MemoryQueue;
+    use ingest_common::testing;
+
+    fn snapshot() -> ActiveMatch {
+        serde_json::from_value(testing::active_match(1, 100)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn publishes_events_and_uploads_batches() {
+        let dir = tempfile::tempdir().unwrap();
+        let broker = MemoryQueue::default();
+        broker.bind_queue("events", "match_events").await.unwrap();
+        let store = LocalStore::new(dir.path().join("store"));
+        let spool = Spool::open(dir.path().join("spool"), store, broker.clone())
+            .await
+            .unwrap();
+
+        let file_name = IngestFileName::new_active_matches(100, 200, true, Some("zst"));
+        spool.seal(&file_name, b"batch").await.unwrap();
+        let snapshot = snapshot();
+        spool
+            .add_event(&MatchEvent::finished(&snapshot, None))
+            .await
+            .unwrap();
+        let uploader = tokio::spawn(spool.clone().run_uploader());
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while broker.is_empty("db_ingest_queue") {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("batch was not uploaded");
+        uploader.abort();
+
+        assert_eq!(broker.len("events"), 1);
+        let object = dir
+            .path()
+            .join("store/ingest/active-matches")
+            .join(file_name.to_string());
+        assert_eq!(std::fs::read(object).unwrap(), b"batch");
+        assert!(spool
+            .pending(&dir.path().join("spool"))
+            .await
+            .unwrap()
+            .is_empty());
+        assert!(spool
+            .pending(&dir.path().join("spool/events"))
+            .await
+            .unwrap()
+            .is_empty());
+    }
+}
@@ -0,0 +1,127 @@
+use crate::ActiveMatch;
+use log::{info, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::io::{BufRead, BufReader, BufWriter, Write};
+use std::path::PathBuf;
+
+/// The log is compacted once it has this many times more records than in-flight snapshots
+const COMPACTION_FACTOR: usize = 4;
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum Record<M> {
+    Snapshot(M),
+    Finished { match_id: u32 },
+}
+
+/// Append-only log of the snapshots of in-flight matches, so their history survives restarts.
+/// Finished matches are marked with a record instead of being removed, the log is rewritten
+/// without them once it grows too large.
+pub struct SnapshotLog {
+    path: PathBuf,
+    file: File,
+    records: usize,
+}
+
+impl SnapshotLog {
+    /// Opens the log and replays it into the snapshots of all in-flight matches.
+    pub fn open(path: PathBuf) -> io::Result<(Self, HashMap<u32, Vec<ActiveMatch>>)> {
+        let mut active_matches: HashMap<u32, Vec<ActiveMatch>> = HashMap::new();
+        match File::open(&path) {
+            Ok(file) => {
+                for line in BufReader::new(file).lines() {
+                    // The last record might be torn if the scraper crashed while writing it
+                    match serde_json::from_str::<Record<ActiveMatch>>(&line?) {
+                        Ok(Record::Snapshot(am)) => {
+                            active_matches.entry(am.match_id).or_default().push(am)
+                        }
+                        Ok(Record::Finished { match_id }) => {
+                            active_matches.remove(&match_id);
+                        }
+                        Err(e) => warn!("Skipping invalid snapshot log record: {:?}", e),
+                    }
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+            Err(e) => return Err(e),
+        }
+        info!(
+            "Restored {} in-flight matches from {:?}",
+            active_matches.len(),
+            path
+        );
+        let mut log = Self {
+            file: OpenOptions::new().create(true).append(true).open(&path)?,
+            path,
+            records: 0,
+        };
+        log.compact(&active_matches)?;
+        Ok((log, active_matches))
+    }
+
+    pub fn append_snapshots<'a>(
+        &mut self,
+        snapshots: impl IntoIterator<Item = &'a ActiveMatch>,
+    ) -> io::Result<()> {
+        self.append(snapshots.into_iter().map(Record::Snapshot))
+    }
+
+    /// Must only be called once the matches are durably written to the batch file.
+    pub fn mark_finished(&mut self, match_ids: impl IntoIterator<Item = u32>) -> io::Result<()> {
+        self.append(
+            match_ids
+                .into_iter()
+                .map(|match_id| Record::Finished { match_id }),
+        )
+    }
+
+    /// Rewrites the log if it mostly consists of finished matches.
+    pub fn compact_if_needed(
+        &mut self,
+        active_matches: &HashMap<u32, Vec<ActiveMatch>>,
+    ) -> io::Result<()> {
+        let snapshots = active_matches.values().map(Vec::len).sum::<usize>();
+        if self.records > COMPACTION_FACTOR * snapshots.max(1) {
+            self.compact(active_matches)?;
+        }
+        Ok(())
+    }
+
+    fn append<'a>(
+        &mut self,
+        records: impl Iterator<Item = Record<&'a ActiveMatch>>,
+    ) -> io::Result<()> {
+        let mut writer = BufWriter::new(&self.file);
+        for record in records {
+            serde_json::to_writer(&mut writer, &record)?;
+            writer.write_all(b"\n")?;
+            self.records += 1;
+        }
+        writer.flush()?;
+        drop(writer);
+        self.file.sync_data()
+    }
+
+    /// Writes the in-flight snapshots to a new file, which atomically replaces the log.
+    fn compact(&mut self, active_matches: &HashMap<u32, Vec<ActiveMatch>>) -> io::Result<()> {
+        let temp_path = self.path.with_extension("compact");
+        let mut writer = BufWriter::new(File::create(&temp_path)?);
+        let mut records = 0;
+        for snapshot in active_matches.values().flatten() {
+            serde_json::to_writer(&mut writer, &Record::Snapshot(snapshot))?;
+            writer.write_all(b"\n")?;
+            records += 1;
+        }
+        writer
+            .into_inner()
+            .map_err(|e| e.into_error())?
+            .sync_all()?;
+        std::fs::rename(&temp_path, &self.path)?;
+        self.file = OpenOptions::new().append(true).open(&self.path)?;
+        self.records = records;
+        Ok(())
+    }
+}
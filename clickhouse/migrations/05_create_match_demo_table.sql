@@ -0,0 +1,13 @@
+CREATE TABLE IF NOT EXISTS match_demo
+(
+    match_id UInt64,
+    map_name String,
+    server_name String,
+    build_num Int32,
+    network_protocol Int32,
+    demo_version_name String,
+    server_start_tick Int32,
+    duration_s Float32,
+    playback_ticks Int32,
+    playback_frames Int32
+) ENGINE = ReplacingMergeTree ORDER BY match_id;
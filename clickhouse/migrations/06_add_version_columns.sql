@@ -0,0 +1,34 @@
+ALTER TABLE match_info
+ADD COLUMN IF NOT EXISTS ingestion_version UInt64 DEFAULT 0,
+ADD COLUMN IF NOT EXISTS parser_version UInt32 DEFAULT 0;
+
+ALTER TABLE match_player
+ADD COLUMN IF NOT EXISTS ingestion_version UInt64 DEFAULT 0,
+ADD COLUMN IF NOT EXISTS parser_version UInt32 DEFAULT 0;
+
+ALTER TABLE active_matches
+ADD COLUMN IF NOT EXISTS ingestion_version UInt64 DEFAULT 0,
+ADD COLUMN IF NOT EXISTS parser_version UInt32 DEFAULT 0;
+
+-- The version column of a ReplacingMergeTree can't be altered, so the tables are recreated.
+-- The copies are truncated first, so this migration can be rerun after a failure. They replace
+-- the original tables in the following single-statement migrations.
+CREATE TABLE IF NOT EXISTS match_info_versioned AS match_info
+ENGINE = ReplacingMergeTree(ingestion_version) ORDER BY match_id
+SETTINGS non_replicated_deduplication_window = 1000;
+TRUNCATE TABLE match_info_versioned;
+INSERT INTO match_info_versioned SELECT * FROM match_info;
+
+CREATE TABLE IF NOT EXISTS match_player_versioned AS match_player
+ENGINE = ReplacingMergeTree(ingestion_version) ORDER BY (match_id, account_id)
+SETTINGS non_replicated_deduplication_window = 1000;
+TRUNCATE TABLE match_player_versioned;
+INSERT INTO match_player_versioned SELECT * FROM match_player;
+
+CREATE TABLE IF NOT EXISTS active_matches_versioned AS active_matches
+ENGINE = ReplacingMergeTree(ingestion_version) ORDER BY (match_id, scraped_at)
+SETTINGS non_replicated_deduplication_window = 1000;
+TRUNCATE TABLE active_matches_versioned;
+INSERT INTO active_matches_versioned SELECT * FROM active_matches;
+
+ALTER TABLE match_demo MODIFY SETTING non_replicated_deduplication_window = 1000;
@@ -0,0 +1 @@
+RENAME TABLE match_info TO match_info_unversioned, match_info_versioned TO match_info;
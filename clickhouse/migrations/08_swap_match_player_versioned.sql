@@ -0,0 +1 @@
+RENAME TABLE match_player TO match_player_unversioned, match_player_versioned TO match_player;
@@ -0,0 +1 @@
+RENAME TABLE active_matches TO active_matches_unversioned, active_matches_versioned TO active_matches;
@@ -0,0 +1,3 @@
+DROP TABLE IF EXISTS match_info_unversioned;
+DROP TABLE IF EXISTS match_player_unversioned;
+DROP TABLE IF EXISTS active_matches_unversioned;
@@ -0,0 +1,15 @@
+ALTER TABLE match_info
+ADD COLUMN IF NOT EXISTS winning_team_raw Int32,
+ADD COLUMN IF NOT EXISTS match_outcome_raw Int32,
+ADD COLUMN IF NOT EXISTS match_mode_raw Int32,
+ADD COLUMN IF NOT EXISTS game_mode_raw Int32,
+ADD COLUMN IF NOT EXISTS objectives.team_objective_raw Array (Int32);
+
+ALTER TABLE match_player
+ADD COLUMN IF NOT EXISTS team_raw Int32;
+
+ALTER TABLE active_matches
+ADD COLUMN IF NOT EXISTS players.team_raw Array (UInt8),
+ADD COLUMN IF NOT EXISTS match_mode_raw UInt8,
+ADD COLUMN IF NOT EXISTS game_mode_raw UInt8,
+ADD COLUMN IF NOT EXISTS region_mode_raw UInt8;
@@ -0,0 +1,23 @@
+CREATE TABLE IF NOT EXISTS match_damage
+(
+    match_id UInt64,
+    dealer_player_slot UInt32,
+    target_player_slot UInt32,
+    source_details_index UInt32,
+    source_name String,
+    stat_type Enum8 (
+        'Unknown' = -1,
+        'Damage' = 0,
+        'Healing' = 1,
+        'HealPrevented' = 2,
+        'Mitigated' = 3,
+        'LethalDamage' = 4
+    ),
+    stat_type_raw Int32,
+    sample_time_s Array (UInt32),
+    damage Array (UInt32),
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, dealer_player_slot, target_player_slot, source_details_index)
+SETTINGS non_replicated_deduplication_window = 1000;
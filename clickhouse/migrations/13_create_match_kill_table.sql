@@ -0,0 +1,28 @@
+CREATE TABLE IF NOT EXISTS match_kill
+(
+    match_id UInt64,
+    game_time_s UInt32,
+    victim_account_id UInt32,
+    victim_player_slot UInt32,
+    victim_hero_id UInt32,
+    victim_team Enum8 (
+        'Team0' = 0,
+        'Team1' = 1,
+        'Spectator' = 16,
+    ),
+    killer_account_id Nullable (UInt32),
+    killer_player_slot UInt32,
+    killer_hero_id Nullable (UInt32),
+    killer_team Nullable (Enum8 (
+        'Team0' = 0,
+        'Team1' = 1,
+        'Spectator' = 16,
+    )),
+    death_pos Tuple (Float32, Float32, Float32),
+    killer_pos Tuple (Float32, Float32, Float32),
+    death_duration_s UInt32,
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, victim_player_slot, game_time_s)
+SETTINGS non_replicated_deduplication_window = 1000;
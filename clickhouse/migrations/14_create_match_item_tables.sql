@@ -0,0 +1,47 @@
+CREATE TABLE IF NOT EXISTS match_item_event
+(
+    match_id UInt64,
+    account_id UInt32,
+    hero_id UInt32,
+    event_type Enum8 (
+        'Buy' = 0,
+        'Sell' = 1,
+    ),
+    game_time_s UInt32,
+    item_id UInt32,
+    flags UInt32,
+    imbued_ability_id UInt32,
+    build_index UInt32,
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, account_id, build_index, event_type)
+SETTINGS non_replicated_deduplication_window = 1000;
+
+CREATE TABLE IF NOT EXISTS match_player_build
+(
+    match_id UInt64,
+    account_id UInt32,
+    hero_id UInt32,
+    build_order Array (UInt32),
+    final_items Array (UInt32),
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, account_id)
+SETTINGS non_replicated_deduplication_window = 1000;
+
+CREATE TABLE IF NOT EXISTS ability_upgrade_order
+(
+    match_id UInt64,
+    account_id UInt32,
+    hero_id UInt32,
+    upgrade_index UInt32,
+    game_time_s UInt32,
+    ability_id UInt32,
+    upgrade_id UInt32,
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, account_id, upgrade_index)
+SETTINGS non_replicated_deduplication_window = 1000;
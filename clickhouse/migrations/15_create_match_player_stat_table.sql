@@ -0,0 +1,17 @@
+CREATE TABLE IF NOT EXISTS match_player_stat
+(
+    match_id UInt64,
+    account_id UInt32,
+    stat_source Enum8 (
+        'StatsTypeStat' = 0,
+        'AbilityStat' = 1,
+    ),
+    stat_index UInt32,
+    stat_name LowCardinality (String),
+    value Float64,
+    mapping_version UInt32,
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY (match_id, account_id, stat_source, stat_index)
+SETTINGS non_replicated_deduplication_window = 1000;
@@ -0,0 +1,10 @@
+CREATE TABLE IF NOT EXISTS match_metadata_envelope
+(
+    match_id UInt64,
+    version Nullable (UInt32),
+    envelope_match_id Nullable (UInt64),
+    ingestion_version UInt64,
+    parser_version UInt32
+) ENGINE = ReplacingMergeTree(ingestion_version)
+ORDER BY match_id
+SETTINGS non_replicated_deduplication_window = 1000;
@@ -0,0 +1,2 @@
+ALTER TABLE active_matches
+ADD COLUMN IF NOT EXISTS history_incomplete Bool DEFAULT false;
@@ -0,0 +1,81 @@
+use crate::models::error::ParseError;
+use crate::models::file_type::FileType;
+use crate::storage;
+use ingest_common::object_store::ObjectStore;
+use log::info;
+use std::collections::HashMap;
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long the current dictionary of a file type is cached, before checking for a rotation
+const CURRENT_REFRESH_INTERVAL: Duration = Duration::from_secs(300);
+
+/// Trained zstd dictionaries are immutable, so they are cached forever by their id
+static DICTIONARIES: LazyLock<Mutex<HashMap<u32, Arc<Dictionary>>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+/// When the current dictionary id of a file type was fetched, and the id
+type CurrentDictionary = (Instant, Option<u32>);
+
+static CURRENT: LazyLock<Mutex<HashMap<FileType, CurrentDictionary>>> =
+    LazyLock::new(|| Mutex::new(HashMap::new()));
+
+#[derive(Debug)]
+pub struct Dictionary {
+    pub data: Vec<u8>,
+}
+
+pub fn get_dictionary_path(id: u32) -> String {
+    format!("/dictionaries/{}.zdict", id)
+}
+
+/// Contains the id of the dictionary new files of the type are compressed with
+pub fn get_current_path(file_type: FileType) -> String {
+    format!("/dictionaries/{}.current", file_type)
+}
+
+/// Returns the dictionary with the id, as stored in the header of zstd frames.
+pub async fn get(store: &impl ObjectStore, id: u32) -> Result<Arc<Dictionary>, ParseError> {
+    if let Some(dictionary) = DICTIONARIES.lock().unwrap().get(&id) {
+        return Ok(dictionary.clone());
+    }
+    info!("Loading zstd dictionary {}", id);
+    let data = storage::download(store, &get_dictionary_path(id)).await?;
+    let dictionary = Arc::new(Dictionary { data });
+    DICTIONARIES.lock().unwrap().insert(id, dictionary.clone());
+    Ok(dictionary)
+}
+
+/// Returns the dictionary new files of the type should be compressed with, if one was trained.
+pub async fn current(
+    store: &impl ObjectStore,
+    file_type: FileType,
+) -> Result<Option<Arc<Dictionary>>, ParseError> {
+    let cached = CURRENT
+        .lock()
+        .unwrap()
+        .get(&file_type)
+        .filter(|(loaded_at, _)| loaded_at.elapsed() < CURRENT_REFRESH_INTERVAL)
+        .map(|(_, id)| *id);
+    let id = match cached {
+        Some(id) => id,
+        None => {
+            let current_path = get_current_path(file_type);
+            let id = if storage::has_file(store, &current_path).await? {
+                let id = storage::download(store, &current_path).await?;
+                String::from_utf8_lossy(&id).trim().parse().ok()
+            } else {
+                None
+            };
+            CURRENT
+                .lock()
+                .unwrap()
+                .insert(file_type, (Instant::now(), id));
+            id
+        }
+    };
+    match id {
+        Some(id) => get(store, id).await.map(Some),
+        None => Ok(None),
+    }
+}
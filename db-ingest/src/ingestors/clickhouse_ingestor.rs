@@ -1,11 +1,28 @@
-use crate::ingestors::ingestor::Ingestor;
+use crate::ingestors::ingestor::{Ingestor, MatchSaltsSource};
 use crate::models::active_match::ActiveMatch;
 use crate::models::clickhouse_active_match::ClickHouseActiveMatch;
+use crate::models::clickhouse_match_damage::ClickhouseMatchDamage;
+use crate::models::clickhouse_match_demo::ClickhouseMatchDemo;
+use crate::models::clickhouse_match_item::{
+    ClickhouseAbilityUpgrade, ClickhouseMatchItemEvent, ClickhouseMatchPlayerBuild, PlayerItemRows,
+};
+use crate::models::clickhouse_match_kill::ClickhouseMatchKill;
 use crate::models::clickhouse_match_metadata::{ClickhouseMatchInfo, ClickhouseMatchPlayer};
-use crate::models::error::ParseError;
-use clickhouse::{Client, Compression};
-use log::debug;
-use std::sync::LazyLock;
+use crate::models::clickhouse_match_metadata_envelope::ClickhouseMatchMetadataEnvelope;
+use crate::models::clickhouse_match_player_stat::ClickhouseMatchPlayerStat;
+use crate::models::demo_info::DemoInfo;
+use crate::models::error::{is_transient_clickhouse_error, ParseError};
+use crate::models::match_metadata::MatchMetadata;
+use crate::models::match_salts::MatchSalts;
+use clickhouse::{Client, Compression, Row};
+use log::{debug, error};
+use prost::Message;
+use serde::Serialize;
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::sync::{Arc, LazyLock, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinSet;
 use valveprotos::deadlock::c_msg_match_meta_data_contents::MatchInfo;
 
 static CLICKHOUSE_URL: LazyLock<String> = LazyLock::new(|| {
@@ -17,12 +34,83 @@ static CLICKHOUSE_PASSWORD: LazyLock<String> =
     LazyLock::new(|| std::env::var("CLICKHOUSE_PASSWORD").unwrap());
 static CLICKHOUSE_DB: LazyLock<String> = LazyLock::new(|| std::env::var("CLICKHOUSE_DB").unwrap());
 
+static BATCH_MAX_ROWS: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("CLICKHOUSE_BATCH_MAX_ROWS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10_000)
+});
+static BATCH_MAX_BYTES: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("CLICKHOUSE_BATCH_MAX_BYTES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(64 * 1024 * 1024)
+});
+static BATCH_MAX_AGE: LazyLock<Duration> = LazyLock::new(|| {
+    Duration::from_secs(
+        std::env::var("CLICKHOUSE_BATCH_MAX_AGE_S")
+            .ok()
+            .and_then(|s| s.parse().ok())
+            .unwrap_or(10),
+    )
+});
+
+const INSERT_ATTEMPTS: u32 = 3;
+
+/// Rows parsed from a single file.
+#[derive(Default)]
+struct FileRows {
+    match_info: Vec<ClickhouseMatchInfo>,
+    match_metadata_envelope: Vec<ClickhouseMatchMetadataEnvelope>,
+    match_player: Vec<ClickhouseMatchPlayer>,
+    match_damage: Vec<ClickhouseMatchDamage>,
+    match_kill: Vec<ClickhouseMatchKill>,
+    match_item_event: Vec<ClickhouseMatchItemEvent>,
+    match_player_build: Vec<ClickhouseMatchPlayerBuild>,
+    ability_upgrade_order: Vec<ClickhouseAbilityUpgrade>,
+    match_player_stat: Vec<ClickhouseMatchPlayerStat>,
+    active_matches: Vec<ClickHouseActiveMatch>,
+    match_demo: Vec<ClickhouseMatchDemo>,
+}
+
+struct PendingFile {
+    dedup_token: String,
+    rows: FileRows,
+    waiter: oneshot::Sender<Result<(), clickhouse::error::Error>>,
+}
+
+/// Files collected from many deliveries, which are flushed together.
+#[derive(Default)]
+struct Batch {
+    files: Vec<PendingFile>,
+    rows: usize,
+    bytes: usize,
+    created_at: Option<Instant>,
+}
+
+impl Batch {
+    fn is_full(&self, max_files: usize) -> bool {
+        self.rows >= *BATCH_MAX_ROWS
+            || self.bytes >= *BATCH_MAX_BYTES
+            || self.files.len() >= max_files
+    }
+
+    fn is_expired(&self) -> bool {
+        self.created_at
+            .is_some_and(|created_at| created_at.elapsed() >= *BATCH_MAX_AGE)
+    }
+}
+
 pub struct ClickhouseIngestor {
     pub client: Client,
+    batch: Mutex<Batch>,
+    max_files: usize,
 }
 
 impl ClickhouseIngestor {
-    pub fn new() -> Self {
+    /// Batches are flushed once they hold `max_files` files, which should be the number of files
+    /// processed concurrently, as no further file can be added before the batch is committed.
+    pub fn new(max_files: usize) -> Self {
         Self {
             client: Client::default()
                 .with_url(CLICKHOUSE_URL.clone())
@@ -30,70 +118,406 @@ impl ClickhouseIngestor {
                 .with_password(CLICKHOUSE_PASSWORD.clone())
                 .with_database(CLICKHOUSE_DB.clone())
                 .with_compression(Compression::None),
+            batch: Mutex::new(Batch::default()),
+            max_files,
+        }
+    }
+
+    /// Periodically flushes batches that exceeded their maximum age.
+    pub async fn run_flush_loop(self: Arc<Self>) {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            self.flush_expired().await;
+        }
+    }
+
+    async fn flush_expired(&self) {
+        let expired_batch = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.is_expired().then(|| std::mem::take(&mut *batch))
+        };
+        if let Some(batch) = expired_batch {
+            self.flush(batch).await;
         }
     }
+
+    /// Adds the rows of a file to the current batch and waits until they are committed.
+    async fn add_to_batch(
+        &self,
+        dedup_token: &str,
+        rows: usize,
+        bytes: usize,
+        add: impl FnOnce(&mut FileRows),
+    ) -> Result<(), ParseError> {
+        let (waiter, receiver) = oneshot::channel();
+        let mut file = PendingFile {
+            dedup_token: dedup_token.to_string(),
+            rows: FileRows::default(),
+            waiter,
+        };
+        add(&mut file.rows);
+        let full_batch = {
+            let mut batch = self.batch.lock().unwrap();
+            batch.files.push(file);
+            batch.rows += rows;
+            batch.bytes += bytes;
+            batch.created_at.get_or_insert_with(Instant::now);
+            batch
+                .is_full(self.max_files)
+                .then(|| std::mem::take(&mut *batch))
+        };
+        if let Some(batch) = full_batch {
+            self.flush(batch).await;
+        }
+        receiver
+            .await
+            .map_err(|_| ParseError::BatchDropped)?
+            .map_err(ParseError::ClickhouseError)
+    }
+
+    /// Inserts the rows of all files with one INSERT per table. If that fails, the files are
+    /// inserted on their own, so rows that ClickHouse rejects only fail the file they came from.
+    async fn flush(&self, batch: Batch) {
+        debug!(
+            "Flushing batch with {} rows ({} bytes) for {} files",
+            batch.rows,
+            batch.bytes,
+            batch.files.len()
+        );
+        let files = batch.files;
+        let rows = files.iter().map(|f| &f.rows).collect::<Vec<_>>();
+        let result = Self::insert(&self.client, &batch_dedup_token(&files), &rows).await;
+        match result {
+            Ok(_) => {
+                for file in files {
+                    let _ = file.waiter.send(Ok(()));
+                }
+            }
+            Err(e) if files.len() == 1 => {
+                error!("Error inserting rows of {}: {}", files[0].dedup_token, e);
+                let _ = files.into_iter().next().unwrap().waiter.send(Err(e));
+            }
+            Err(e) => {
+                error!(
+                    "Error inserting batch of {} files, inserting them on their own: {}",
+                    files.len(),
+                    e
+                );
+                let mut inserts = JoinSet::new();
+                for file in files {
+                    let client = self.client.clone();
+                    inserts.spawn(async move {
+                        let result = Self::insert(&client, &file.dedup_token, &[&file.rows]).await;
+                        if let Err(e) = &result {
+                            error!("Error inserting rows of {}: {}", file.dedup_token, e);
+                        }
+                        let _ = file.waiter.send(result);
+                    });
+                }
+                while inserts.join_next().await.is_some() {}
+            }
+        }
+    }
+
+    /// Inserts the rows of the files, retrying transient errors. Retries are deduplicated by the
+    /// `dedup_token`, even if some tables were already written.
+    async fn insert(
+        client: &Client,
+        dedup_token: &str,
+        files: &[&FileRows],
+    ) -> Result<(), clickhouse::error::Error> {
+        let client = client
+            .clone()
+            .with_option("insert_deduplication_token", dedup_token);
+        let mut attempt = 1;
+        loop {
+            match Self::insert_tables(&client, files).await {
+                Ok(_) => return Ok(()),
+                Err(e) if is_transient_clickhouse_error(&e) && attempt < INSERT_ATTEMPTS => {
+                    error!(
+                        "Error inserting rows of {} (attempt {}): {}",
+                        dedup_token, attempt, e
+                    );
+                    tokio::time::sleep(Duration::from_secs(attempt as u64)).await;
+                    attempt += 1;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    async fn insert_tables(
+        client: &Client,
+        files: &[&FileRows],
+    ) -> Result<(), clickhouse::error::Error> {
+        Self::insert_rows(client, "match_info", files, |f| &f.match_info).await?;
+        Self::insert_rows(client, "match_metadata_envelope", files, |f| {
+            &f.match_metadata_envelope
+        })
+        .await?;
+        Self::insert_rows(client, "match_player", files, |f| &f.match_player).await?;
+        Self::insert_rows(client, "match_damage", files, |f| &f.match_damage).await?;
+        Self::insert_rows(client, "match_kill", files, |f| &f.match_kill).await?;
+        Self::insert_rows(client, "match_item_event", files, |f| &f.match_item_event).await?;
+        Self::insert_rows(client, "match_player_build", files, |f| {
+            &f.match_player_build
+        })
+        .await?;
+        Self::insert_rows(client, "ability_upgrade_order", files, |f| {
+            &f.ability_upgrade_order
+        })
+        .await?;
+        Self::insert_rows(client, "match_player_stat", files, |f| &f.match_player_stat).await?;
+        Self::insert_rows(client, "active_matches", files, |f| &f.active_matches).await?;
+        Self::insert_rows(client, "match_demo", files, |f| &f.match_demo).await?;
+        Ok(())
+    }
+
+    async fn insert_rows<T: Row + Serialize>(
+        client: &Client,
+        table: &str,
+        files: &[&FileRows],
+        rows: impl Fn(&FileRows) -> &Vec<T>,
+    ) -> Result<(), clickhouse::error::Error> {
+        if files.iter().all(|f| rows(f).is_empty()) {
+            return Ok(());
+        }
+        let mut insert = client.insert(table)?;
+        for row in files.iter().flat_map(|f| rows(f)) {
+            insert.write(row).await?;
+        }
+        insert.end().await
+    }
 }
 
-impl Ingestor<MatchInfo> for ClickhouseIngestor {
-    async fn ingest(&self, match_info: &MatchInfo) -> Result<(), ParseError> {
-        let mut match_info_insert = self
-            .client
-            .insert("match_info")
-            .map_err(ParseError::ClickhouseError)?;
-        let mut match_player_insert = self
-            .client
-            .insert("match_player")
-            .map_err(ParseError::ClickhouseError)?;
-        let ch_match_metadata: ClickhouseMatchInfo = match_info.clone().into();
-        match_info_insert
-            .write(&ch_match_metadata)
+/// Identifies the files of a batch, so retries of its inserts are deduplicated. Only retries
+/// within this process insert the same batch again, so the hash doesn't need to be stable.
+fn batch_dedup_token(files: &[PendingFile]) -> String {
+    let mut tokens = files.iter().map(|f| &f.dedup_token).collect::<Vec<_>>();
+    tokens.sort();
+    let mut hasher = DefaultHasher::new();
+    tokens.hash(&mut hasher);
+    format!("batch.{:016x}", hasher.finish())
+}
+
+impl MatchSaltsSource for ClickhouseIngestor {
+    async fn get_match_salts(&self, match_id: u64) -> Result<Option<MatchSalts>, ParseError> {
+        self.client
+            .query("SELECT ?fields FROM match_salts FINAL WHERE match_id = ?")
+            .bind(match_id)
+            .fetch_optional()
             .await
-            .map_err(ParseError::ClickhouseError)?;
+            .map_err(ParseError::ClickhouseError)
+    }
+}
 
+impl Ingestor<MatchMetadata> for ClickhouseIngestor {
+    async fn ingest(&self, metadata: &MatchMetadata, dedup_token: &str) -> Result<(), ParseError> {
+        let envelope = ClickhouseMatchMetadataEnvelope::from(metadata);
+        self.ingest_match_info(&metadata.match_info, Some(envelope), dedup_token)
+            .await
+    }
+}
+
+impl Ingestor<MatchInfo> for ClickhouseIngestor {
+    async fn ingest(&self, match_info: &MatchInfo, dedup_token: &str) -> Result<(), ParseError> {
+        self.ingest_match_info(match_info, None, dedup_token).await
+    }
+}
+
+impl ClickhouseIngestor {
+    async fn ingest_match_info(
+        &self,
+        match_info: &MatchInfo,
+        envelope: Option<ClickhouseMatchMetadataEnvelope>,
+        dedup_token: &str,
+    ) -> Result<(), ParseError> {
+        let ch_match_info = ClickhouseMatchInfo::try_from(match_info.clone())?;
         let ch_players = match_info
             .players
             .iter()
-            .map::<ClickhouseMatchPlayer, _>(|p| (match_info.match_id(), p.clone()).into());
-        for player in ch_players {
-            match_player_insert
-                .write(&player)
-                .await
-                .map_err(ParseError::ClickhouseError)?;
-        }
-        match_info_insert
-            .end()
-            .await
-            .map_err(ParseError::ClickhouseError)?;
-        match_player_insert
-            .end()
-            .await
-            .map_err(ParseError::ClickhouseError)?;
-        Ok(())
+            .enumerate()
+            .map(|(i, p)| {
+                ClickhouseMatchPlayer::try_from((match_info.match_id(), p.clone())).map_err(|e| {
+                    match e {
+                        ParseError::InvalidField(field) => {
+                            ParseError::InvalidField(format!("players[{}].{}", i, field))
+                        }
+                        e => e,
+                    }
+                })
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let ch_damage = match_info
+            .damage_matrix
+            .as_ref()
+            .map(|m| ClickhouseMatchDamage::from_damage_matrix(match_info.match_id(), m))
+            .transpose()?
+            .unwrap_or_default();
+        let ch_kills = ClickhouseMatchKill::from_match_info(match_info)?;
+        let ch_items = match_info
+            .players
+            .iter()
+            .map(|p| PlayerItemRows::from((match_info.match_id(), p)))
+            .collect::<Vec<_>>();
+        let item_rows = ch_items
+            .iter()
+            .map(|i| i.item_events.len() + 1 + i.ability_upgrades.len())
+            .sum::<usize>();
+        let ch_player_stats = match_info
+            .players
+            .iter()
+            .flat_map(|p| {
+                ClickhouseMatchPlayerStat::from_player(
+                    match_info.match_id(),
+                    match_info.start_time(),
+                    p,
+                )
+            })
+            .collect::<Vec<_>>();
+        self.add_to_batch(
+            dedup_token,
+            1 + envelope.iter().len()
+                + ch_players.len()
+                + ch_damage.len()
+                + ch_kills.len()
+                + item_rows
+                + ch_player_stats.len(),
+            match_info.encoded_len(),
+            |rows| {
+                rows.match_info.push(ch_match_info);
+                rows.match_metadata_envelope.extend(envelope);
+                rows.match_player.extend(ch_players);
+                rows.match_damage.extend(ch_damage);
+                rows.match_kill.extend(ch_kills);
+                for items in ch_items {
+                    rows.match_item_event.extend(items.item_events);
+                    rows.match_player_build.push(items.build);
+                    rows.ability_upgrade_order.extend(items.ability_upgrades);
+                }
+                rows.match_player_stat.extend(ch_player_stats);
+            },
+        )
+        .await
     }
 }
 
 impl Ingestor<Vec<ActiveMatch>> for ClickhouseIngestor {
-    async fn ingest(&self, active_matches: &Vec<ActiveMatch>) -> Result<(), ParseError> {
+    async fn ingest(
+        &self,
+        active_matches: &Vec<ActiveMatch>,
+        dedup_token: &str,
+    ) -> Result<(), ParseError> {
         debug!("Ingesting {} active matches", active_matches.len());
-        let mut active_match_insert = self
-            .client
-            .insert("active_matches")
-            .map_err(ParseError::ClickhouseError)?;
         let ch_active_matches: Vec<ClickHouseActiveMatch> = active_matches
             .iter()
             .cloned()
             .map(ClickHouseActiveMatch::from)
             .collect();
-        for active_match in ch_active_matches {
-            active_match_insert
-                .write(&active_match)
-                .await
-                .map_err(ParseError::ClickhouseError)?;
+        let bytes = active_matches
+            .iter()
+            .map(|am| size_of_val(am) + size_of_val(am.players.as_slice()))
+            .sum();
+        self.add_to_batch(dedup_token, ch_active_matches.len(), bytes, |rows| {
+            rows.active_matches.extend(ch_active_matches)
+        })
+        .await
+    }
+}
+
+impl Ingestor<DemoInfo> for ClickhouseIngestor {
+    async fn ingest(&self, demo_info: &DemoInfo, dedup_token: &str) -> Result<(), ParseError> {
+        debug!("Ingesting demo info for match {}", demo_info.match_id);
+        let ch_match_demo: ClickhouseMatchDemo = demo_info.clone().into();
+        let bytes = demo_info.header.encoded_len() + demo_info.file_info.encoded_len();
+        self.add_to_batch(dedup_token, 1, bytes, |rows| {
+            rows.match_demo.push(ch_match_demo)
+        })
+        .await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clickhouse::test::{handlers, status, Mock};
+
+    fn ingestor(mock: &Mock, max_files: usize) -> ClickhouseIngestor {
+        ClickhouseIngestor {
+            client: Client::default().with_url(mock.url()),
+            batch: Mutex::new(Batch::default()),
+            max_files,
         }
-        active_match_insert
-            .end()
-            .await
-            .map_err(ParseError::ClickhouseError)?;
-        Ok(())
+    }
+
+    fn demo_info(match_id: u64) -> DemoInfo {
+        DemoInfo {
+            match_id,
+            header: Default::default(),
+            file_info: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn inserts_the_files_of_a_batch_together() {
+        let mock = Mock::new();
+        let inserted = mock.add(handlers::record::<ClickhouseMatchDemo>());
+        let ingestor = ingestor(&mock, 2);
+        let (first_demo, second_demo) = (demo_info(1), demo_info(2));
+
+        let (first, second) = tokio::join!(
+            ingestor.ingest(&first_demo, "1.dem.v1"),
+            ingestor.ingest(&second_demo, "2.dem.v1"),
+        );
+        first.unwrap();
+        second.unwrap();
+
+        let rows: Vec<ClickhouseMatchDemo> = inserted.collect().await;
+        assert_eq!(rows.iter().map(|r| r.match_id).collect::<Vec<_>>(), [1, 2]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn inserts_the_files_on_their_own_if_the_batch_fails() {
+        let mock = Mock::new();
+        for _ in 0..INSERT_ATTEMPTS {
+            mock.add(handlers::failure(status::SERVICE_UNAVAILABLE));
+        }
+        let first = mock.add(handlers::record::<ClickhouseMatchDemo>());
+        let second = mock.add(handlers::record::<ClickhouseMatchDemo>());
+        let ingestor = ingestor(&mock, 2);
+        let (first_demo, second_demo) = (demo_info(1), demo_info(2));
+
+        let (first_result, second_result) = tokio::join!(
+            ingestor.ingest(&first_demo, "1.dem.v1"),
+            ingestor.ingest(&second_demo, "2.dem.v1"),
+        );
+        first_result.unwrap();
+        second_result.unwrap();
+
+        let mut match_ids = vec![];
+        for inserted in [first, second] {
+            let rows: Vec<ClickhouseMatchDemo> = inserted.collect().await;
+            match_ids.extend(rows.iter().map(|r| r.match_id));
+        }
+        match_ids.sort();
+        assert_eq!(match_ids, [1, 2]);
+    }
+
+    #[test]
+    fn batch_tokens_ignore_the_order_of_files() {
+        let file = |dedup_token: &str| PendingFile {
+            dedup_token: dedup_token.to_string(),
+            rows: FileRows::default(),
+            waiter: oneshot::channel().0,
+        };
+        assert_eq!(
+            batch_dedup_token(&[file("1.dem.v1"), file("2.dem.v1")]),
+            batch_dedup_token(&[file("2.dem.v1"), file("1.dem.v1")])
+        );
+        assert_ne!(
+            batch_dedup_token(&[file("1.dem.v1")]),
+            batch_dedup_token(&[file("1.dem.v1"), file("2.dem.v1")])
+        );
     }
 }
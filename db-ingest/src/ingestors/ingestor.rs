@@ -1,5 +1,45 @@
 use crate::models::error::ParseError;
+use crate::models::match_salts::MatchSalts;
+use std::future::Future;
 
 pub trait Ingestor<T>: Send {
-    async fn ingest(&self, data: &T) -> Result<(), ParseError>;
+    /// The `dedup_token` identifies the source file, so retried inserts of it are deduplicated.
+    fn ingest(
+        &self,
+        data: &T,
+        dedup_token: &str,
+    ) -> impl Future<Output = Result<(), ParseError>> + Send;
+}
+
+/// Provides the salts of matches, which match files are validated against.
+pub trait MatchSaltsSource: Send + Sync {
+    fn get_match_salts(
+        &self,
+        match_id: u64,
+    ) -> impl Future<Output = Result<Option<MatchSalts>, ParseError>> + Send;
+}
+
+/// Records the dedup tokens of the ingested files, without salts for any match.
+#[cfg(test)]
+#[derive(Default)]
+pub(crate) struct RecordingIngestor {
+    pub(crate) dedup_tokens: std::sync::Mutex<Vec<String>>,
+}
+
+#[cfg(test)]
+impl<T: Sync> Ingestor<T> for RecordingIngestor {
+    async fn ingest(&self, _: &T, dedup_token: &str) -> Result<(), ParseError> {
+        self.dedup_tokens
+            .lock()
+            .unwrap()
+            .push(dedup_token.to_string());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl MatchSaltsSource for RecordingIngestor {
+    async fn get_match_salts(&self, _: u64) -> Result<Option<MatchSalts>, ParseError> {
+        Ok(None)
+    }
 }
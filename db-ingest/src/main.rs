@@ -1,124 +1,358 @@
 use crate::ingestors::clickhouse_ingestor::ClickhouseIngestor;
-use crate::ingestors::ingestor::Ingestor;
+use crate::ingestors::ingestor::MatchSaltsSource;
 use crate::models::compression::Compression;
 use crate::models::error::ParseError;
 use crate::models::file_data::FileData;
 use crate::models::file_type::FileType;
-use crate::parsers::active_matches_json_lines_parser::ActiveMatchesJsonLinesParser;
-use crate::parsers::metadata_content_parser::MetaDataContentParser;
-use crate::parsers::metadata_parser::MetaDataParser;
-use crate::parsers::parser::Parser;
-use ::s3::request::ResponseData;
+use crate::parsers::registry::{ParserRegistry, PARSERS};
+use crate::reprocess::ReprocessArgs;
+use crate::train_dictionary::TrainDictionaryArgs;
+use clap::Parser as _;
 use futures_lite::StreamExt;
-use lapin::message::Delivery;
-use lapin::options::{BasicAckOptions, BasicRejectOptions};
-use log::{debug, error, info};
+use ingest_common::object_store::{ObjectStore, Store};
+use ingest_common::queue::{Broker, Delivery, Headers, Queue};
+use log::{debug, error, info, warn};
+use metrics_exporter_prometheus::PrometheusBuilder;
 use std::path::Path;
-use std::sync::Arc;
+use std::sync::{Arc, LazyLock};
+use tokio::io::AsyncBufReadExt;
 use tokio::sync::Semaphore;
 
+mod dictionaries;
 mod ingestors;
 mod models;
 mod parsers;
-mod rmq;
-mod s3;
+mod reprocess;
+mod retry;
+mod storage;
+mod train_dictionary;
+mod validation;
+
+/// Larger files are rejected, to defend against decompression bombs
+pub(crate) static MAX_DECOMPRESSED_SIZE: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("MAX_DECOMPRESSED_SIZE")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(1024 * 1024 * 1024)
+});
+
+/// Deliveries are only acked once their batch is committed, so this also bounds the batch size
+static MAX_CONCURRENT_MESSAGES: LazyLock<usize> = LazyLock::new(|| {
+    std::env::var("MAX_CONCURRENT_MESSAGES")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(10)
+});
 
 #[tokio::main]
 async fn main() {
     env_logger::init();
 
-    let mut db_ingest_queue_consumer = match rmq::get_queue_consumer("db_ingest_queue").await {
+    let store = match Store::from_env() {
+        Ok(store) => Arc::new(store),
+        Err(e) => panic!("Error configuring object store: {}", e),
+    };
+    // An invalid value fails at startup instead of at the first file
+    LazyLock::force(&validation::UNVERIFIED_FILES);
+
+    match std::env::args().nth(1).as_deref() {
+        Some("reprocess") => {
+            let args = ReprocessArgs::parse_from(std::env::args().skip(1));
+            reprocess::run(args, store).await;
+            return;
+        }
+        Some("train-dictionary") => {
+            let args = TrainDictionaryArgs::parse_from(std::env::args().skip(1));
+            train_dictionary::run(args, store.as_ref()).await;
+            return;
+        }
+        _ => {}
+    }
+
+    if let Err(e) = PrometheusBuilder::new()
+        .with_http_listener(([0, 0, 0, 0], 8080))
+        .install()
+    {
+        panic!("Error installing metrics exporter: {:?}", e);
+    }
+
+    let broker = match Broker::from_env("%2f") {
+        Ok(broker) => Arc::new(broker),
+        Err(e) => panic!("Error configuring queue: {}", e),
+    };
+
+    for attempt in 1..*retry::MAX_ATTEMPTS {
+        let retry_queue = retry::get_retry_queue("db_ingest_queue", attempt);
+        let delay = retry::get_retry_delay(attempt);
+        if let Err(e) = broker
+            .declare_delay_queue(&retry_queue, "db_ingest_queue", delay)
+            .await
+        {
+            panic!("Error declaring retry queue {}: {:?}", retry_queue, e);
+        }
+    }
+
+    let mut db_ingest_queue_consumer = match broker.consume("db_ingest_queue").await {
         Ok(c) => c,
         Err(e) => panic!("Error getting queue consumer: {:?}", e),
     };
 
-    let semaphore = Arc::new(Semaphore::new(10));
+    let ingestor = Arc::new(ClickhouseIngestor::new(*MAX_CONCURRENT_MESSAGES));
+    tokio::spawn(ingestor.clone().run_flush_loop());
+
+    let semaphore = Arc::new(Semaphore::new(*MAX_CONCURRENT_MESSAGES));
     while let Some(delivery) = db_ingest_queue_consumer.next().await {
         let semaphore = semaphore.clone();
         match delivery {
             Ok(delivery) => {
                 let permit = semaphore.clone().acquire_owned().await.unwrap();
+                let ingestor = ingestor.clone();
+                let store = store.clone();
+                let broker = broker.clone();
+                let acker = delivery.acker.clone();
+                let task = tokio::spawn(async move {
+                    process_message(
+                        &PARSERS,
+                        ingestor.as_ref(),
+                        store.as_ref(),
+                        broker.as_ref(),
+                        delivery,
+                    )
+                    .await;
+                });
                 tokio::spawn(async move {
-                    process_message(delivery).await;
+                    // Make sure a panicking task doesn't leave its delivery unacknowledged
+                    if let Err(e) = task.await {
+                        error!("Error processing message, task failed: {:?}", e);
+                        if let Err(e) = acker.reject(false).await {
+                            error!("Error rejecting message: {}", e);
+                        }
+                    }
                     drop(permit);
                 });
             }
             Err(e) => {
-                error!("Error receiving message: {:?}", e);
+                error!("Error receiving message: {}", e);
                 continue;
             }
         }
     }
 }
 
-async fn process_message(message: Delivery) {
-    match try_process_message(&message).await {
+async fn process_message<I: MatchSaltsSource>(
+    parsers: &ParserRegistry<I>,
+    ingestor: &I,
+    store: &impl ObjectStore,
+    broker: &impl Queue,
+    message: Delivery,
+) {
+    let attempt = retry::get_attempt(&message) + 1;
+    let is_last_attempt = attempt >= *retry::MAX_ATTEMPTS;
+    match try_process_message(parsers, ingestor, store, broker, &message, is_last_attempt).await {
         Ok(_) => {
             debug!("Message processed successfully");
-            message.ack(BasicAckOptions::default()).await.unwrap();
+            message.ack().await.unwrap();
+        }
+        Err(e) if e.is_transient() && !is_last_attempt => {
+            warn!(
+                "Transient error processing message (attempt {}/{}), retrying in {:?}: {}",
+                attempt,
+                *retry::MAX_ATTEMPTS,
+                retry::get_retry_delay(attempt),
+                e
+            );
+            match retry::schedule_retry(broker, "db_ingest_queue", &message.data, attempt).await {
+                Ok(_) => message.ack().await.unwrap(),
+                Err(e) => {
+                    error!("Error scheduling retry, requeueing message: {}", e);
+                    message.reject(true).await.unwrap();
+                }
+            }
         }
         Err(e) => {
-            error!("Error processing message: {:?}", e);
-            message.reject(BasicRejectOptions::default()).await.unwrap();
+            error!("Error processing message (attempt {}): {}", attempt, e);
+            message.reject(false).await.unwrap();
         }
     }
 }
 
-async fn try_process_message(message: &Delivery) -> Result<(), ParseError> {
-    let s3_path = String::from_utf8_lossy(&message.data);
-    let s3_path = s3_path.trim();
-    let s3_path = Path::new(s3_path);
-    info!("Processing file: {:?}", s3_path);
-    let file_data = FileData::try_from(&s3_path.to_path_buf())?;
+async fn try_process_message<I: MatchSaltsSource>(
+    parsers: &ParserRegistry<I>,
+    ingestor: &I,
+    store: &impl ObjectStore,
+    broker: &impl Queue,
+    message: &Delivery,
+    is_last_attempt: bool,
+) -> Result<(), ParseError> {
+    let object_path = String::from_utf8_lossy(&message.data);
+    let object_path = object_path.trim();
+    let object_path = Path::new(object_path);
+    info!("Processing file: {:?}", object_path);
+    let file_data = parsers.file_data(object_path)?;
     debug!("File Data: {:#?}", file_data);
-    let s3_path = file_data
+    let object_path = file_data
         .file_path
         .to_str()
         .ok_or(ParseError::FilenameParse)?;
-    let file_content = s3::download_from_s3(s3_path).await?;
-    match process_file(&file_data, &file_content).await {
-        Ok(_) => Ok(()),
+    match process_file(parsers, ingestor, store, &file_data, object_path).await {
+        Ok(_) => {
+            storage::delete(store, object_path).await?;
+            Ok(())
+        }
+        Err(e) if e.is_transient() && !is_last_attempt => Err(e),
+        Err(ParseError::InvalidField(field)) => {
+            let quarantine_path = get_stored_path(
+                "quarantine",
+                &file_data.file_name,
+                file_data.file_type,
+                file_data.compression,
+            );
+            warn!("Quarantining {}, invalid field: {}", quarantine_path, field);
+            let reason = format!("Invalid field: {}", field);
+            move_with_reason(store, object_path, &quarantine_path, &reason).await?;
+            Err(ParseError::InvalidField(field))
+        }
+        Err(ParseError::Suspicious(reason)) => {
+            let suspicious_path = get_stored_path(
+                "suspicious",
+                &file_data.file_name,
+                file_data.file_type,
+                file_data.compression,
+            );
+            warn!("Rejecting suspicious file {}: {}", suspicious_path, reason);
+            move_with_reason(store, object_path, &suspicious_path, &reason).await?;
+            Err(ParseError::Suspicious(reason))
+        }
         Err(e) => {
             let failed_path = get_failed_path(
                 &file_data.file_name,
                 file_data.file_type,
                 file_data.compression,
             );
-            s3::delete_from_s3(s3_path).await?;
-            if !s3::has_file(&failed_path).await.unwrap_or_default() {
-                s3::upload_to_s3(file_content.bytes(), &failed_path).await?;
-                rmq::add_to_queue("parse_error_queue", &failed_path).await?;
+            if !storage::has_file(store, &failed_path)
+                .await
+                .unwrap_or_default()
+            {
+                storage::copy(store, object_path, &failed_path).await?;
+                broker
+                    .publish("parse_error_queue", failed_path.as_bytes(), &Headers::new())
+                    .await
+                    .map_err(ParseError::Queue)?;
             }
+            storage::delete(store, object_path).await?;
             Err(e)
         }
     }
 }
 
-async fn process_file(file_data: &FileData, file_content: &ResponseData) -> Result<(), ParseError> {
-    let decompressed = file_data
-        .compression
-        .decompress(&file_content.bytes().to_vec())
+async fn process_file<I: MatchSaltsSource>(
+    parsers: &ParserRegistry<I>,
+    ingestor: &I,
+    store: &impl ObjectStore,
+    file_data: &FileData,
+    object_path: &str,
+) -> Result<(), ParseError> {
+    let parser = parsers
+        .get(file_data.file_type)
+        .ok_or(ParseError::UnknownVariant)?;
+    info!("Processing {} file", file_data.file_type);
+    let mut reader = storage::download_stream(store, object_path).await?;
+    let compression = Compression::detect(
+        reader.fill_buf().await.map_err(ParseError::Io)?,
+        file_data.compression,
+    )?;
+    let decompressed = compression
+        .decompress_stream(store, reader, *MAX_DECOMPRESSED_SIZE)
         .await?;
-    match file_data.file_type {
-        FileType::Metadata => {
-            process_metadata(file_data, decompressed.as_slice(), file_content.as_slice()).await
-        }
-        FileType::MetadataContent => {
-            process_metadata_content(file_data, decompressed.as_slice(), file_content.as_slice())
-                .await
-        }
-        FileType::ActiveMatchesJsonLines => {
-            process_active_matches(file_data, decompressed.as_slice(), file_content.as_slice())
-                .await
-        }
+    let result = parser
+        .parse_and_ingest(ingestor, file_data, &decompressed)
+        .await?;
+    let parsed_path = get_parsed_path(&file_data.file_name, result.file_type, result.compression);
+    let dictionary = match result.compression {
+        Compression::Zstd => dictionaries::current(store, result.file_type).await?,
+        _ => None,
+    };
+    if result.data.is_none() && compression == result.compression && dictionary.is_none() {
+        debug!("No changes detected, moving file to parsed");
+        copy_unless_same(store, object_path, &parsed_path).await?;
+    } else {
+        let data = match result.data {
+            Some(data) => {
+                drop(decompressed);
+                data
+            }
+            None => decompressed,
+        };
+        let mut compressed = result
+            .compression
+            .compress_stream(&data, dictionary.as_deref())
+            .await?;
+        storage::upload_stream(store, &mut compressed, &parsed_path).await?;
     }
+    if result.keep_original {
+        copy_unless_same(
+            store,
+            object_path,
+            &get_parsed_path(
+                &file_data.file_name,
+                file_data.file_type,
+                file_data.compression,
+            ),
+        )
+        .await?;
+    }
+    Ok(())
 }
 
 fn get_parsed_path(file_name: &str, file_type: FileType, compression: Compression) -> String {
+    get_stored_path("parsed", file_name, file_type, compression)
+}
+
+/// Copies the object, unless it already is the target, as when reprocessing `/parsed/`. S3 rejects
+/// copying an object onto itself.
+async fn copy_unless_same(
+    store: &impl ObjectStore,
+    object_path: &str,
+    target_path: &str,
+) -> Result<(), ParseError> {
+    if object_path.trim_start_matches('/') == target_path.trim_start_matches('/') {
+        debug!("{} is already stored at its target", object_path);
+        return Ok(());
+    }
+    storage::copy(store, object_path, target_path).await
+}
+
+/// Moves a file that must not be ingested, next to a `.reason` file explaining why.
+async fn move_with_reason(
+    store: &impl ObjectStore,
+    object_path: &str,
+    target_path: &str,
+    reason: &str,
+) -> Result<(), ParseError> {
+    storage::copy(store, object_path, target_path).await?;
+    storage::upload(store, reason.as_bytes(), &format!("{}.reason", target_path)).await?;
+    storage::delete(store, object_path).await?;
+    Ok(())
+}
+
+/// Path of a file stored under `prefix`, named so it parses back into the same [`FileData`].
+fn get_stored_path(
+    prefix: &str,
+    file_name: &str,
+    file_type: FileType,
+    compression: Compression,
+) -> String {
     match compression {
-        Compression::Uncompressed => format!("/parsed/{}/{}.{}", file_type, file_name, file_type),
+        Compression::Uncompressed => format!(
+            "/{}/{}/{}.{}",
+            prefix,
+            file_type,
+            file_name,
+            file_type.extension()
+        ),
         _ => format!(
-            "/parsed/{}/{}.{}.{}",
+            "/{}/{}/{}.{}.{}",
+            prefix,
             file_type,
             file_name,
             file_type.extension(),
@@ -128,101 +362,83 @@ fn get_parsed_path(file_name: &str, file_type: FileType, compression: Compressio
 }
 
 fn get_failed_path(file_name: &str, file_type: FileType, compression: Compression) -> String {
-    match compression {
-        Compression::Uncompressed => format!("/failed/{}/{}.{}", file_type, file_name, file_type),
-        _ => format!(
-            "/failed/{}/{}.{}.{}",
-            file_type, file_name, file_type, compression
-        ),
-    }
+    get_stored_path("failed", file_name, file_type, compression)
 }
 
-async fn process_metadata(
-    file_data: &FileData,
-    decompressed: &[u8],
-    file_content: &[u8],
-) -> Result<(), ParseError> {
-    info!("Processing metadata file");
-    let result = MetaDataParser.parse(file_data, decompressed)?;
-    let compressed = if file_data.compression == result.compression && file_content == result.data {
-        debug!("No changes detected, moving file to parsed");
-        file_content
-    } else {
-        &result.compression.compress(&result.data).await?
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestors::ingestor::RecordingIngestor;
+    use crate::parsers::active_matches_delta_json_lines_parser::ACTIVE_MATCHES_DELTA;
+    use crate::parsers::active_matches_json_lines_parser::{
+        ActiveMatchesJsonLinesParser, ACTIVE_MATCHES,
     };
-    ClickhouseIngestor::new()
-        .ingest(&result.parsed_data)
-        .await?;
+    use ingest_common::object_store::LocalStore;
+    use ingest_common::queue::MemoryQueue;
+    use ingest_common::testing;
+    use serde_json::json;
 
-    s3::upload_to_s3(
-        compressed,
-        &get_parsed_path(&file_data.file_name, result.file_type, result.compression),
-    )
-    .await?;
-    let s3_path = file_data
-        .file_path
-        .to_str()
-        .ok_or(ParseError::FilenameParse)?;
-    s3::delete_from_s3(s3_path).await?;
-    Ok(())
-}
+    #[test]
+    fn stored_paths_parse_back() {
+        let file_types = [ACTIVE_MATCHES, ACTIVE_MATCHES_DELTA];
+        for (file_type, compression) in file_types
+            .into_iter()
+            .flat_map(|t| [(t, Compression::Uncompressed), (t, Compression::Zstd)])
+        {
+            for prefix in ["parsed", "failed", "quarantine", "suspicious"] {
+                let path = get_stored_path(prefix, "100-142", file_type, compression);
+                let file_data = PARSERS.file_data(Path::new(&path)).unwrap();
+                assert_eq!(file_data.file_name, "100-142", "{}", path);
+                assert_eq!(file_data.file_type, file_type, "{}", path);
+                assert_eq!(file_data.compression, compression, "{}", path);
+            }
+        }
+        assert_eq!(
+            get_stored_path(
+                "quarantine",
+                "100-142",
+                ACTIVE_MATCHES_DELTA,
+                Compression::Zstd
+            ),
+            "/quarantine/active-matches-delta/100-142.amdjsonl.zst"
+        );
+    }
 
-async fn process_metadata_content(
-    file_data: &FileData,
-    decompressed: &[u8],
-    file_content: &[u8],
-) -> Result<(), ParseError> {
-    info!("Processing metadata content file");
-    let result = MetaDataContentParser.parse(file_data, decompressed)?;
-    let compressed = if file_data.compression == result.compression && file_content == result.data {
-        debug!("No changes detected, moving file to parsed");
-        file_content
-    } else {
-        &result.compression.compress(&result.data).await?
-    };
-    ClickhouseIngestor::new()
-        .ingest(&result.parsed_data)
-        .await?;
+    #[tokio::test]
+    async fn processes_a_message_into_parsed_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(dir.path());
+        let broker = MemoryQueue::default();
+        let mut parsers = ParserRegistry::default();
+        parsers.register(ActiveMatchesJsonLinesParser);
+        let ingestor = RecordingIngestor::default();
 
-    s3::upload_to_s3(
-        compressed,
-        &get_parsed_path(&file_data.file_name, result.file_type, result.compression),
-    )
-    .await?;
-    let s3_path = file_data
-        .file_path
-        .to_str()
-        .ok_or(ParseError::FilenameParse)?;
-    s3::delete_from_s3(s3_path).await?;
-    Ok(())
-}
+        let object_path = "/ingest/active-matches/100-142.amjsonl";
+        let matches = [testing::active_match(1, 100), testing::active_match(2, 100)];
+        let data = json!(matches).to_string() + "\n";
+        storage::upload(&store, data.as_bytes(), object_path)
+            .await
+            .unwrap();
+        broker
+            .publish("db_ingest_queue", object_path.as_bytes(), &Headers::new())
+            .await
+            .unwrap();
+        let mut deliveries = broker.consume("db_ingest_queue").await.unwrap();
+        let delivery = deliveries.next().await.unwrap().unwrap();
 
-async fn process_active_matches(
-    file_data: &FileData,
-    decompressed: &[u8],
-    file_content: &[u8],
-) -> Result<(), ParseError> {
-    info!("Processing active matches file");
-    let result = ActiveMatchesJsonLinesParser.parse(file_data, decompressed)?;
-    let compressed = if file_data.compression == result.compression && file_content == result.data {
-        debug!("No changes detected, moving file to parsed");
-        file_content
-    } else {
-        &result.compression.compress(&result.data).await?
-    };
-    let active_matches = result.parsed_data.into_iter().flatten().collect::<Vec<_>>();
-    debug!("Active Matches: {:#?}", active_matches.len());
-    ClickhouseIngestor::new().ingest(&active_matches).await?;
-
-    s3::upload_to_s3(
-        compressed,
-        &get_parsed_path(&file_data.file_name, result.file_type, result.compression),
-    )
-    .await?;
-    let s3_path = file_data
-        .file_path
-        .to_str()
-        .ok_or(ParseError::FilenameParse)?;
-    s3::delete_from_s3(s3_path).await?;
-    Ok(())
+        process_message(&parsers, &ingestor, &store, &broker, delivery).await;
+
+        let file_data = parsers.file_data(Path::new(object_path)).unwrap();
+        assert_eq!(
+            *ingestor.dedup_tokens.lock().unwrap(),
+            [file_data.dedup_token()]
+        );
+        let parsed_path =
+            get_parsed_path(&file_data.file_name, file_data.file_type, Compression::Zstd);
+        assert!(storage::has_file(&store, &parsed_path).await.unwrap());
+        assert!(!storage::has_file(&store, object_path).await.unwrap());
+        // Acked instead of requeued or retried
+        assert!(broker.is_empty("db_ingest_queue"));
+        assert!(broker.is_empty("parse_error_queue"));
+    }
 }
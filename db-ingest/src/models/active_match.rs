@@ -19,6 +19,9 @@ pub struct ActiveMatch {
     pub game_mode: u8,
     pub match_score: u32,
     pub region_mode: u8,
+    /// Set by the scraper if snapshots are missing, because the match finished while it was down
+    #[serde(default)]
+    pub history_incomplete: bool,
 }
 
 #[derive(Debug, Deserialize, Serialize, Clone)]
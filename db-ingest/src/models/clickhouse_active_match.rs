@@ -1,5 +1,6 @@
 use crate::models::active_match::ActiveMatch;
 use crate::models::enums::{GameMode, MatchMode, RegionMode, Team};
+use crate::models::version::{ingestion_version, PARSER_VERSION};
 use clickhouse::Row;
 use serde::Serialize;
 
@@ -29,6 +30,14 @@ pub struct ClickHouseActiveMatch {
     pub game_mode: GameMode,
     pub match_score: u32,
     pub region_mode: RegionMode,
+    #[serde(rename = "players.team_raw")]
+    pub players_team_raw: Vec<u8>,
+    pub match_mode_raw: u8,
+    pub game_mode_raw: u8,
+    pub region_mode_raw: u8,
+    pub history_incomplete: bool,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
 }
 
 impl From<ActiveMatch> for ClickHouseActiveMatch {
@@ -54,6 +63,13 @@ impl From<ActiveMatch> for ClickHouseActiveMatch {
             game_mode: GameMode::from(am.game_mode),
             match_score: am.match_score,
             region_mode: RegionMode::from(am.region_mode),
+            players_team_raw: am.players.iter().map(|p| p.team).collect(),
+            match_mode_raw: am.match_mode,
+            game_mode_raw: am.game_mode,
+            region_mode_raw: am.region_mode,
+            history_incomplete: am.history_incomplete,
+            ingestion_version: ingestion_version(),
+            parser_version: PARSER_VERSION,
         }
     }
 }
@@ -0,0 +1,171 @@
+use crate::models::enums::{raw_proto_value, StatType};
+use crate::models::error::ParseError;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
+use clickhouse::Row;
+use serde::Serialize;
+use valveprotos::deadlock::c_msg_match_player_damage_matrix::EStatType;
+use valveprotos::deadlock::CMsgMatchPlayerDamageMatrix;
+
+/// One damage time series of the damage matrix, the values correspond to `sample_time_s`.
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchDamage {
+    pub match_id: u64,
+    pub dealer_player_slot: u32,
+    pub target_player_slot: u32,
+    pub source_details_index: u32,
+    pub source_name: String,
+    pub stat_type: StatType,
+    pub stat_type_raw: i32,
+    pub sample_time_s: Vec<u32>,
+    pub damage: Vec<u32>,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+impl ClickhouseMatchDamage {
+    /// Flattens the damage matrix into one row per dealer, source and target.
+    pub fn from_damage_matrix(
+        match_id: u64,
+        damage_matrix: &CMsgMatchPlayerDamageMatrix,
+    ) -> Result<Vec<Self>, ParseError> {
+        let source_details = damage_matrix
+            .source_details
+            .as_ref()
+            .ok_or_else(|| ParseError::InvalidField("damage_matrix.source_details".to_string()))?;
+        let ingestion_version = ingestion_version();
+        let mut rows = vec![];
+        for (i, dealer) in damage_matrix.damage_dealers.iter().enumerate() {
+            for (j, source) in dealer.damage_sources.iter().enumerate() {
+                let source_details_index = source.source_details_index();
+                let index = source_details_index as usize;
+                let (Some(stat_type), Some(source_name)) = (
+                    source_details.stat_type.get(index),
+                    source_details.source_name.get(index),
+                ) else {
+                    return Err(ParseError::InvalidField(format!(
+                        "damage_matrix.damage_dealers[{}].damage_sources[{}].source_details_index",
+                        i, j
+                    )));
+                };
+                let stat_type_raw = raw_proto_value::<EStatType>("EStatType", Some(*stat_type));
+                let stat_type =
+                    EStatType::try_from(stat_type_raw).map_or(StatType::Unknown, StatType::from);
+                for target in &source.damage_to_players {
+                    rows.push(Self {
+                        match_id,
+                        dealer_player_slot: dealer.dealer_player_slot(),
+                        target_player_slot: target.target_player_slot(),
+                        source_details_index,
+                        source_name: source_name.clone(),
+                        stat_type: stat_type.clone(),
+                        stat_type_raw,
+                        sample_time_s: damage_matrix.sample_time_s.clone(),
+                        damage: target.damage.clone(),
+                        ingestion_version,
+                        parser_version: PARSER_VERSION,
+                    });
+                }
+            }
+        }
+        Ok(rows)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::metadata_parser::MetaDataParser;
+    use crate::parsers::parser::Parser;
+    use crate::parsers::registry::PARSERS;
+    use std::path::{Path, PathBuf};
+    use valveprotos::deadlock::c_msg_match_player_damage_matrix::{DamageDealer, DamageSource};
+
+    const SAMPLE: &str = "T002_M31452_C185_S1764893.meta";
+
+    #[test]
+    fn flattens_the_damage_matrix_of_a_sample() {
+        let data = std::fs::read(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("testdata")
+                .join(SAMPLE),
+        )
+        .unwrap();
+        let file_data = PARSERS.file_data(Path::new(SAMPLE)).unwrap();
+        let match_info = MetaDataParser
+            .parse(&file_data, &data)
+            .unwrap()
+            .parsed_data
+            .match_info;
+        let rows = ClickhouseMatchDamage::from_damage_matrix(
+            match_info.match_id(),
+            match_info.damage_matrix.as_ref().unwrap(),
+        )
+        .unwrap();
+
+        let rows = rows
+            .iter()
+            .map(|r| {
+                assert_eq!(r.match_id, 31452);
+                assert_eq!(r.sample_time_s, vec![180, 360]);
+                (
+                    r.dealer_player_slot,
+                    r.target_player_slot,
+                    r.source_name.as_str(),
+                    r.stat_type.clone(),
+                    r.stat_type_raw,
+                    r.damage.clone(),
+                )
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            rows,
+            vec![
+                (
+                    1,
+                    7,
+                    "citadel_weapon_haze_set",
+                    StatType::Damage,
+                    0,
+                    vec![100, 250]
+                ),
+                (
+                    1,
+                    8,
+                    "citadel_weapon_haze_set",
+                    StatType::Damage,
+                    0,
+                    vec![0, 60]
+                ),
+                (
+                    1,
+                    1,
+                    "upgrade_healing_rite",
+                    StatType::Healing,
+                    1,
+                    vec![0, 40]
+                ),
+                // Unknown stat types keep their raw value
+                (7, 1, "unknown_source", StatType::Unknown, 7, vec![5, 5]),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_sources_without_details() {
+        let damage_matrix = CMsgMatchPlayerDamageMatrix {
+            damage_dealers: vec![DamageDealer {
+                dealer_player_slot: Some(1),
+                damage_sources: vec![DamageSource {
+                    source_details_index: Some(3),
+                    damage_to_players: vec![],
+                }],
+            }],
+            source_details: Some(Default::default()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            ClickhouseMatchDamage::from_damage_matrix(1, &damage_matrix),
+            Err(ParseError::InvalidField(_))
+        ));
+    }
+}
@@ -0,0 +1,34 @@
+use crate::models::demo_info::DemoInfo;
+use clickhouse::Row;
+use serde::{Deserialize, Serialize};
+
+#[derive(Row, Debug, Serialize, Deserialize)]
+pub struct ClickhouseMatchDemo {
+    pub match_id: u64,
+    pub map_name: String,
+    pub server_name: String,
+    pub build_num: i32,
+    pub network_protocol: i32,
+    pub demo_version_name: String,
+    pub server_start_tick: i32,
+    pub duration_s: f32,
+    pub playback_ticks: i32,
+    pub playback_frames: i32,
+}
+
+impl From<DemoInfo> for ClickhouseMatchDemo {
+    fn from(value: DemoInfo) -> Self {
+        Self {
+            match_id: value.match_id,
+            map_name: value.header.map_name().to_string(),
+            server_name: value.header.server_name().to_string(),
+            build_num: value.header.build_num(),
+            network_protocol: value.header.network_protocol(),
+            demo_version_name: value.header.demo_version_name().to_string(),
+            server_start_tick: value.header.server_start_tick(),
+            duration_s: value.file_info.playback_time(),
+            playback_ticks: value.file_info.playback_ticks(),
+            playback_frames: value.file_info.playback_frames(),
+        }
+    }
+}
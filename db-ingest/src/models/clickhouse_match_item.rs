@@ -0,0 +1,118 @@
+use crate::models::enums::ItemEventType;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
+use clickhouse::Row;
+use serde::Serialize;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::{Items, Players};
+
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchItemEvent {
+    pub match_id: u64,
+    pub account_id: u32,
+    pub hero_id: u32,
+    pub event_type: ItemEventType,
+    pub game_time_s: u32,
+    pub item_id: u32,
+    pub flags: u32,
+    pub imbued_ability_id: u32,
+    /// Position of the purchase in the player's build, the same for the buy and sell event
+    pub build_index: u32,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchPlayerBuild {
+    pub match_id: u64,
+    pub account_id: u32,
+    pub hero_id: u32,
+    /// Item ids in the order they were bought
+    pub build_order: Vec<u32>,
+    /// Item ids that were never sold, in the order they were bought
+    pub final_items: Vec<u32>,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseAbilityUpgrade {
+    pub match_id: u64,
+    pub account_id: u32,
+    pub hero_id: u32,
+    pub upgrade_index: u32,
+    pub game_time_s: u32,
+    pub ability_id: u32,
+    pub upgrade_id: u32,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+/// Rows derived from the `items` of a player.
+pub struct PlayerItemRows {
+    pub item_events: Vec<ClickhouseMatchItemEvent>,
+    pub build: ClickhouseMatchPlayerBuild,
+    pub ability_upgrades: Vec<ClickhouseAbilityUpgrade>,
+}
+
+impl From<(u64, &Players)> for PlayerItemRows {
+    fn from((match_id, player): (u64, &Players)) -> Self {
+        let ingestion_version = ingestion_version();
+        let mut items = player.items.iter().collect::<Vec<_>>();
+        items.sort_by_key(|i| i.game_time_s());
+        // Ability upgrades are part of the items, but are the only entries with an upgrade id
+        let (ability_upgrades, purchases): (Vec<&Items>, Vec<&Items>) =
+            items.into_iter().partition(|i| i.upgrade_id() != 0);
+
+        let mut item_events = vec![];
+        for (build_index, item) in purchases.iter().enumerate() {
+            let event = |event_type, game_time_s| ClickhouseMatchItemEvent {
+                match_id,
+                account_id: player.account_id(),
+                hero_id: player.hero_id(),
+                event_type,
+                game_time_s,
+                item_id: item.item_id(),
+                flags: item.flags(),
+                imbued_ability_id: item.imbued_ability_id(),
+                build_index: build_index as u32,
+                ingestion_version,
+                parser_version: PARSER_VERSION,
+            };
+            item_events.push(event(ItemEventType::Buy, item.game_time_s()));
+            if item.sold_time_s() > 0 {
+                item_events.push(event(ItemEventType::Sell, item.sold_time_s()));
+            }
+        }
+
+        Self {
+            item_events,
+            build: ClickhouseMatchPlayerBuild {
+                match_id,
+                account_id: player.account_id(),
+                hero_id: player.hero_id(),
+                build_order: purchases.iter().map(|i| i.item_id()).collect(),
+                final_items: purchases
+                    .iter()
+                    .filter(|i| i.sold_time_s() == 0)
+                    .map(|i| i.item_id())
+                    .collect(),
+                ingestion_version,
+                parser_version: PARSER_VERSION,
+            },
+            ability_upgrades: ability_upgrades
+                .into_iter()
+                .enumerate()
+                .map(|(upgrade_index, item)| ClickhouseAbilityUpgrade {
+                    match_id,
+                    account_id: player.account_id(),
+                    hero_id: player.hero_id(),
+                    upgrade_index: upgrade_index as u32,
+                    game_time_s: item.game_time_s(),
+                    ability_id: item.item_id(),
+                    upgrade_id: item.upgrade_id(),
+                    ingestion_version,
+                    parser_version: PARSER_VERSION,
+                })
+                .collect(),
+        }
+    }
+}
@@ -0,0 +1,76 @@
+use crate::models::enums::Team;
+use crate::models::error::ParseError;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
+use clickhouse::Row;
+use serde::Serialize;
+use std::collections::HashMap;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::{MatchInfo, Players, Position};
+
+/// A death of a player, with the killer resolved from `killer_player_slot`.
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchKill {
+    pub match_id: u64,
+    pub game_time_s: u32,
+    pub victim_account_id: u32,
+    pub victim_player_slot: u32,
+    pub victim_hero_id: u32,
+    pub victim_team: Team,
+    /// The killer is `None` if the slot does not belong to a player, e.g. for creep kills
+    pub killer_account_id: Option<u32>,
+    pub killer_player_slot: u32,
+    pub killer_hero_id: Option<u32>,
+    pub killer_team: Option<Team>,
+    pub death_pos: (f32, f32, f32),
+    pub killer_pos: (f32, f32, f32),
+    pub death_duration_s: u32,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+impl ClickhouseMatchKill {
+    pub fn from_match_info(match_info: &MatchInfo) -> Result<Vec<Self>, ParseError> {
+        let players_by_slot: HashMap<u32, &Players> = match_info
+            .players
+            .iter()
+            .map(|p| (p.player_slot(), p))
+            .collect();
+        let ingestion_version = ingestion_version();
+        let mut rows = vec![];
+        for (i, victim) in match_info.players.iter().enumerate() {
+            for (j, death) in victim.death_details.iter().enumerate() {
+                let killer = players_by_slot.get(&death.killer_player_slot());
+                rows.push(Self {
+                    match_id: match_info.match_id(),
+                    game_time_s: death.game_time_s(),
+                    victim_account_id: victim.account_id(),
+                    victim_player_slot: victim.player_slot(),
+                    victim_hero_id: victim.hero_id(),
+                    victim_team: Team::from(victim.team()),
+                    killer_account_id: killer.map(|k| k.account_id()),
+                    killer_player_slot: death.killer_player_slot(),
+                    killer_hero_id: killer.map(|k| k.hero_id()),
+                    killer_team: killer.map(|k| Team::from(k.team())),
+                    death_pos: get_position(death.death_pos, || {
+                        format!("players[{}].death_details[{}].death_pos", i, j)
+                    })?,
+                    killer_pos: get_position(death.killer_pos, || {
+                        format!("players[{}].death_details[{}].killer_pos", i, j)
+                    })?,
+                    death_duration_s: death.death_duration_s(),
+                    ingestion_version,
+                    parser_version: PARSER_VERSION,
+                });
+            }
+        }
+        Ok(rows)
+    }
+}
+
+fn get_position(
+    position: Option<Position>,
+    field: impl FnOnce() -> String,
+) -> Result<(f32, f32, f32), ParseError> {
+    position
+        .map(|p| (p.x(), p.y(), p.z()))
+        .ok_or_else(|| ParseError::InvalidField(field()))
+}
@@ -1,8 +1,12 @@
-use crate::models::enums::{GameMode, MatchMode, MatchOutcome, Objective, Team};
+use crate::models::enums::{raw_proto_value, GameMode, MatchMode, MatchOutcome, Objective, Team};
+use crate::models::error::ParseError;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
 use clickhouse::Row;
 use serde::Serialize;
-use valveprotos::deadlock::c_msg_match_meta_data_contents::MatchInfo;
-use valveprotos::deadlock::c_msg_match_meta_data_contents::Players;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::{EMatchOutcome, MatchInfo, Players};
+use valveprotos::deadlock::{
+    ECitadelGameMode, ECitadelLobbyTeam, ECitadelMatchMode, ECitadelTeamObjective,
+};
 
 #[derive(Row, Debug, Serialize)]
 pub struct ClickhouseMatchInfo {
@@ -40,11 +44,30 @@ pub struct ClickhouseMatchInfo {
     pub mid_boss_team_claimed: Vec<Team>,
     #[serde(rename = "mid_boss.destroyed_time_s")]
     pub mid_boss_destroyed_time_s: Vec<u32>,
+    pub winning_team_raw: i32,
+    pub match_outcome_raw: i32,
+    pub match_mode_raw: i32,
+    pub game_mode_raw: i32,
+    #[serde(rename = "objectives.team_objective_raw")]
+    pub objectives_team_objective_raw: Vec<i32>,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
 }
 
-impl From<MatchInfo> for ClickhouseMatchInfo {
-    fn from(value: MatchInfo) -> Self {
-        Self {
+impl TryFrom<MatchInfo> for ClickhouseMatchInfo {
+    type Error = ParseError;
+
+    fn try_from(value: MatchInfo) -> Result<Self, Self::Error> {
+        let source_details = value
+            .damage_matrix
+            .as_ref()
+            .map(|v| {
+                v.source_details.clone().ok_or(ParseError::InvalidField(
+                    "damage_matrix.source_details".to_string(),
+                ))
+            })
+            .transpose()?;
+        Ok(Self {
             match_id: value.match_id(),
             duration_s: value.duration_s(),
             match_outcome: MatchOutcome::from(value.match_outcome()),
@@ -91,16 +114,11 @@ impl From<MatchInfo> for ClickhouseMatchInfo {
                 .as_ref()
                 .map(|v| v.clone().sample_time_s)
                 .unwrap_or_default(),
-            stat_type: value
-                .damage_matrix
-                .as_ref()
-                .map(|v| v.clone().source_details.unwrap().stat_type)
-                .unwrap_or_default(),
-            source_name: value
-                .damage_matrix
+            stat_type: source_details
                 .as_ref()
-                .map(|v| v.clone().source_details.unwrap().source_name)
+                .map(|v| v.stat_type.clone())
                 .unwrap_or_default(),
+            source_name: source_details.map(|v| v.source_name).unwrap_or_default(),
             objectives_mask_team0: value.objectives_mask_team0() as u16,
             objectives_mask_team1: value.objectives_mask_team1() as u16,
             mid_boss_team_killed: value
@@ -120,7 +138,32 @@ impl From<MatchInfo> for ClickhouseMatchInfo {
                 .iter()
                 .map(|v| v.destroyed_time_s())
                 .collect(),
-        }
+            winning_team_raw: raw_proto_value::<ECitadelLobbyTeam>(
+                "ECitadelLobbyTeam",
+                value.winning_team,
+            ),
+            match_outcome_raw: raw_proto_value::<EMatchOutcome>(
+                "EMatchOutcome",
+                value.match_outcome,
+            ),
+            match_mode_raw: raw_proto_value::<ECitadelMatchMode>(
+                "ECitadelMatchMode",
+                value.match_mode,
+            ),
+            game_mode_raw: raw_proto_value::<ECitadelGameMode>("ECitadelGameMode", value.game_mode),
+            objectives_team_objective_raw: value
+                .objectives
+                .iter()
+                .map(|v| {
+                    raw_proto_value::<ECitadelTeamObjective>(
+                        "ECitadelTeamObjective",
+                        v.team_objective_id,
+                    )
+                })
+                .collect(),
+            ingestion_version: ingestion_version(),
+            parser_version: PARSER_VERSION,
+        })
     }
 }
 
@@ -254,11 +297,16 @@ pub struct ClickhouseMatchPlayer {
     pub stats_damage_mitigated: Vec<u32>,
     #[serde(rename = "stats.level")]
     pub stats_level: Vec<u32>,
+    pub team_raw: i32,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
 }
 
-impl From<(u64, Players)> for ClickhouseMatchPlayer {
-    fn from((match_id, value): (u64, Players)) -> Self {
-        Self {
+impl TryFrom<(u64, Players)> for ClickhouseMatchPlayer {
+    type Error = ParseError;
+
+    fn try_from((match_id, value): (u64, Players)) -> Result<Self, Self::Error> {
+        Ok(Self {
             match_id,
             account_id: value.account_id(),
             player_slot: value.player_slot(),
@@ -275,25 +323,23 @@ impl From<(u64, Players)> for ClickhouseMatchPlayer {
             death_details_death_pos: value
                 .death_details
                 .iter()
-                .map(|v| {
-                    (
-                        v.death_pos.unwrap().x(),
-                        v.death_pos.unwrap().y(),
-                        v.death_pos.unwrap().z(),
-                    )
+                .enumerate()
+                .map(|(i, v)| {
+                    v.death_pos.map(|p| (p.x(), p.y(), p.z())).ok_or_else(|| {
+                        ParseError::InvalidField(format!("death_details[{}].death_pos", i))
+                    })
                 })
-                .collect(),
+                .collect::<Result<_, _>>()?,
             death_details_killer_pos: value
                 .death_details
                 .iter()
-                .map(|v| {
-                    (
-                        v.killer_pos.unwrap().x(),
-                        v.killer_pos.unwrap().y(),
-                        v.killer_pos.unwrap().z(),
-                    )
+                .enumerate()
+                .map(|(i, v)| {
+                    v.killer_pos.map(|p| (p.x(), p.y(), p.z())).ok_or_else(|| {
+                        ParseError::InvalidField(format!("death_details[{}].killer_pos", i))
+                    })
                 })
-                .collect(),
+                .collect::<Result<_, _>>()?,
             death_details_death_duration_s: value
                 .death_details
                 .iter()
@@ -388,6 +434,9 @@ impl From<(u64, Players)> for ClickhouseMatchPlayer {
             book_reward_xp_amount: value.book_rewards.iter().map(|v| v.xp_amount()).collect(),
             book_reward_book_id: value.book_rewards.iter().map(|v| v.book_id()).collect(),
             abandon_match_time_s: value.abandon_match_time_s(),
-        }
+            team_raw: raw_proto_value::<ECitadelLobbyTeam>("ECitadelLobbyTeam", value.team),
+            ingestion_version: ingestion_version(),
+            parser_version: PARSER_VERSION,
+        })
     }
 }
@@ -0,0 +1,25 @@
+use crate::models::match_metadata::MatchMetadata;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
+use clickhouse::Row;
+use serde::Serialize;
+
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchMetadataEnvelope {
+    pub match_id: u64,
+    pub version: Option<u32>,
+    pub envelope_match_id: Option<u64>,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+impl From<&MatchMetadata> for ClickhouseMatchMetadataEnvelope {
+    fn from(value: &MatchMetadata) -> Self {
+        Self {
+            match_id: value.match_info.match_id(),
+            version: value.version,
+            envelope_match_id: value.match_id,
+            ingestion_version: ingestion_version(),
+            parser_version: PARSER_VERSION,
+        }
+    }
+}
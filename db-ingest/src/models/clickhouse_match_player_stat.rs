@@ -0,0 +1,55 @@
+use crate::models::enums::StatSource;
+use crate::models::stat_names::StatNameMapping;
+use crate::models::version::{ingestion_version, PARSER_VERSION};
+use clickhouse::Row;
+use serde::Serialize;
+use valveprotos::deadlock::c_msg_match_meta_data_contents::Players;
+
+#[derive(Row, Debug, Serialize)]
+pub struct ClickhouseMatchPlayerStat {
+    pub match_id: u64,
+    pub account_id: u32,
+    pub stat_source: StatSource,
+    /// Index into `stats_type_stat` or the ability id, kept to relabel rows with newer mappings
+    pub stat_index: u32,
+    pub stat_name: String,
+    pub value: f64,
+    pub mapping_version: u32,
+    pub ingestion_version: u64,
+    pub parser_version: u32,
+}
+
+impl ClickhouseMatchPlayerStat {
+    pub fn from_player(match_id: u64, start_time: u32, player: &Players) -> Vec<Self> {
+        let mapping = StatNameMapping::for_match(start_time);
+        let ingestion_version = ingestion_version();
+        let row = |stat_source, stat_index, stat_name, value| Self {
+            match_id,
+            account_id: player.account_id(),
+            stat_source,
+            stat_index,
+            stat_name,
+            value,
+            mapping_version: mapping.version,
+            ingestion_version,
+            parser_version: PARSER_VERSION,
+        };
+        let stats_type_stats = player.stats_type_stat.iter().enumerate().map(|(i, v)| {
+            row(
+                StatSource::StatsTypeStat,
+                i as u32,
+                mapping.stats_type_stat_name(i),
+                *v as f64,
+            )
+        });
+        let ability_stats = player.ability_stats.iter().map(|s| {
+            row(
+                StatSource::AbilityStat,
+                s.ability_id(),
+                mapping.ability_stat_name(s.ability_id()),
+                s.ability_value() as f64,
+            )
+        });
+        stats_type_stats.chain(ability_stats).collect()
+    }
+}
@@ -1,10 +1,21 @@
+use crate::dictionaries;
+use crate::dictionaries::Dictionary;
 use crate::models::error::ParseError;
-use async_compression::tokio::bufread::{BzDecoder, ZstdDecoder};
-use async_compression::tokio::write::{BzEncoder, ZstdEncoder};
+use async_compression::tokio::bufread;
+use async_compression::tokio::bufread::{BzDecoder, GzipDecoder, XzDecoder, ZstdDecoder};
+use async_compression::tokio::write::{BzEncoder, GzipEncoder, XzEncoder, ZstdEncoder};
+use async_compression::Level;
+use ingest_common::object_store::ObjectStore;
 use std::fmt::Display;
+use std::io::{Read, Write};
 use std::str::FromStr;
 use std::{vec, write};
-use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::io;
+use tokio::io::{AsyncBufRead, AsyncRead, AsyncReadExt, AsyncWriteExt};
+use tokio_util::io::SyncIoBridge;
+
+/// The longest zstd frame header, which contains the dictionary id
+const ZSTD_FRAME_HEADER_MAX_SIZE: u64 = 18;
 
 #[derive(Debug, Default, Clone, Copy, Eq, PartialEq)]
 pub enum Compression {
@@ -12,30 +23,131 @@ pub enum Compression {
     Uncompressed,
     Bzip2,
     Zstd,
+    Gzip,
+    Xz,
+    Lz4,
 }
 
 impl Compression {
-    pub async fn decompress(&self, data: &Vec<u8>) -> Result<Vec<u8>, ParseError> {
-        match self {
-            Self::Uncompressed => Ok(data.clone()),
-            Self::Bzip2 => {
-                let mut decompressed = vec![];
-                BzDecoder::new(data.as_ref())
-                    .read_to_end(&mut decompressed)
-                    .await
-                    .map_err(ParseError::Decompress)?;
-                Ok(decompressed)
-            }
-            Self::Zstd => {
-                let mut decompressed = vec![];
-                ZstdDecoder::new(data.as_ref())
-                    .read_to_end(&mut decompressed)
-                    .await
-                    .map_err(ParseError::Decompress)?;
-                Ok(decompressed)
+    const MAGIC_BYTES: [(Self, &'static [u8]); 5] = [
+        (Self::Bzip2, b"BZh"),
+        (Self::Zstd, &[0x28, 0xb5, 0x2f, 0xfd]),
+        (Self::Gzip, &[0x1f, 0x8b]),
+        (Self::Xz, &[0xfd, 0x37, 0x7a, 0x58, 0x5a, 0x00]),
+        (Self::Lz4, &[0x04, 0x22, 0x4d, 0x18]),
+    ];
+
+    /// Detects the compression from the magic bytes at the start of the data.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        Self::MAGIC_BYTES
+            .iter()
+            .find(|(_, magic)| data.starts_with(magic))
+            .map(|(compression, _)| *compression)
+    }
+
+    /// Returns the compression of the data, falling back to the one from the file extension if
+    /// the magic bytes are unknown. Fails if the magic bytes contradict a compressed extension.
+    /// Files without a compression extension are trusted to be uncompressed, as uncompressed data
+    /// may start with anything, including magic bytes.
+    pub fn detect(data: &[u8], extension: Self) -> Result<Self, ParseError> {
+        if extension == Self::Uncompressed {
+            return Ok(extension);
+        }
+        match Self::sniff(data) {
+            Some(detected) if detected != extension => Err(ParseError::CompressionMismatch {
+                extension,
+                detected,
+            }),
+            Some(detected) => Ok(detected),
+            None => Ok(extension),
+        }
+    }
+
+    /// Decompresses the data while it is read, failing once more than `max_size` bytes were
+    /// decompressed, to defend against decompression bombs. zstd frames compressed with a trained
+    /// dictionary are decompressed with the dictionary of the id in their header.
+    pub async fn decompress_stream<R>(
+        &self,
+        store: &impl ObjectStore,
+        mut reader: R,
+        max_size: u64,
+    ) -> Result<Vec<u8>, ParseError>
+    where
+        R: AsyncBufRead + Send + Unpin + 'static,
+    {
+        // A single read may return less than the frame header, so it is read upfront
+        let mut header = vec![];
+        (&mut reader)
+            .take(ZSTD_FRAME_HEADER_MAX_SIZE)
+            .read_to_end(&mut header)
+            .await
+            .map_err(ParseError::Io)?;
+        let dictionary = match self {
+            Self::Zstd => match zstd::zstd_safe::get_dict_id_from_frame(&header) {
+                Some(id) => Some(dictionaries::get(store, id.get()).await?),
+                None => None,
+            },
+            _ => None,
+        };
+        let reader = AsyncReadExt::chain(std::io::Cursor::new(header), reader);
+        let decompressed = match self {
+            Self::Uncompressed => read_limited(reader, max_size).await,
+            Self::Bzip2 => read_limited(BzDecoder::new(reader), max_size).await,
+            Self::Zstd => match dictionary {
+                Some(dictionary) => {
+                    let decoder = ZstdDecoder::with_dict(reader, &dictionary.data)
+                        .map_err(ParseError::Decompress)?;
+                    read_limited(decoder, max_size).await
+                }
+                None => read_limited(ZstdDecoder::new(reader), max_size).await,
+            },
+            Self::Gzip => read_limited(GzipDecoder::new(reader), max_size).await,
+            Self::Xz => read_limited(XzDecoder::new(reader), max_size).await,
+            Self::Lz4 => {
+                // There is no async lz4 decoder, so it runs on a blocking thread instead
+                let reader = SyncIoBridge::new(reader);
+                tokio::task::spawn_blocking(move || {
+                    let mut decompressed = vec![];
+                    lz4_flex::frame::FrameDecoder::new(reader)
+                        .take(max_size + 1)
+                        .read_to_end(&mut decompressed)
+                        .map(|_| decompressed)
+                })
+                .await
+                .map_err(|e| ParseError::Decompress(io::Error::other(e)))?
             }
         }
+        .map_err(ParseError::Decompress)?;
+        if decompressed.len() as u64 > max_size {
+            return Err(ParseError::DecompressedTooLarge(max_size));
+        }
+        Ok(decompressed)
     }
+
+    /// Compresses the data while it is read, e.g. by a streaming upload. The dictionary is only
+    /// used for zstd.
+    pub async fn compress_stream<'a>(
+        &self,
+        data: &'a [u8],
+        dictionary: Option<&Dictionary>,
+    ) -> Result<Box<dyn AsyncRead + Send + Unpin + 'a>, ParseError> {
+        Ok(match self {
+            Self::Uncompressed => Box::new(data),
+            Self::Bzip2 => Box::new(bufread::BzEncoder::new(data)),
+            Self::Zstd => match dictionary {
+                Some(dictionary) => Box::new(
+                    bufread::ZstdEncoder::with_dict(data, Level::Default, &dictionary.data)
+                        .map_err(ParseError::Decompress)?,
+                ),
+                None => Box::new(bufread::ZstdEncoder::new(data)),
+            },
+            Self::Gzip => Box::new(bufread::GzipEncoder::new(data)),
+            Self::Xz => Box::new(bufread::XzEncoder::new(data)),
+            // There is no async lz4 encoder, so the data is compressed upfront
+            Self::Lz4 => Box::new(std::io::Cursor::new(self.compress(data).await?)),
+        })
+    }
+
     pub async fn compress(&self, data: &[u8]) -> Result<Vec<u8>, ParseError> {
         match self {
             Self::Uncompressed => Ok(data.to_vec()),
@@ -57,16 +169,50 @@ impl Compression {
                 encoder.shutdown().await.map_err(ParseError::Decompress)?;
                 Ok(encoder.into_inner())
             }
+            Self::Gzip => {
+                let mut encoder = GzipEncoder::new(Vec::new());
+                encoder
+                    .write_all(data)
+                    .await
+                    .map_err(ParseError::Decompress)?;
+                encoder.shutdown().await.map_err(ParseError::Decompress)?;
+                Ok(encoder.into_inner())
+            }
+            Self::Xz => {
+                let mut encoder = XzEncoder::new(Vec::new());
+                encoder
+                    .write_all(data)
+                    .await
+                    .map_err(ParseError::Decompress)?;
+                encoder.shutdown().await.map_err(ParseError::Decompress)?;
+                Ok(encoder.into_inner())
+            }
+            Self::Lz4 => {
+                let mut encoder = lz4_flex::frame::FrameEncoder::new(Vec::new());
+                encoder.write_all(data).map_err(ParseError::Decompress)?;
+                encoder
+                    .finish()
+                    .map_err(|e| ParseError::Decompress(e.into()))
+            }
         }
     }
 }
 
+async fn read_limited<R: AsyncRead + Unpin>(reader: R, max_size: u64) -> io::Result<Vec<u8>> {
+    let mut data = vec![];
+    reader.take(max_size + 1).read_to_end(&mut data).await?;
+    Ok(data)
+}
+
 impl Display for Compression {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
             Self::Uncompressed => write!(f, ""),
             Self::Bzip2 => write!(f, "bz2"),
             Self::Zstd => write!(f, "zst"),
+            Self::Gzip => write!(f, "gz"),
+            Self::Xz => write!(f, "xz"),
+            Self::Lz4 => write!(f, "lz4"),
         }
     }
 }
@@ -78,7 +224,77 @@ impl FromStr for Compression {
         match s {
             "bz2" => Ok(Self::Bzip2),
             "zst" => Ok(Self::Zstd),
+            "gz" => Ok(Self::Gzip),
+            "xz" => Ok(Self::Xz),
+            "lz4" => Ok(Self::Lz4),
             _ => Err(ParseError::UnknownVariant),
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage;
+    use ingest_common::object_store::LocalStore;
+    use tokio::io::BufReader;
+
+    #[test]
+    fn detect_only_fails_for_compressed_extensions() {
+        let zstd = [0x28, 0xb5, 0x2f, 0xfd, 0x00];
+        assert_eq!(
+            Compression::detect(&zstd, Compression::Zstd).unwrap(),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::detect(b"plain", Compression::Zstd).unwrap(),
+            Compression::Zstd
+        );
+        assert_eq!(
+            Compression::detect(&zstd, Compression::Uncompressed).unwrap(),
+            Compression::Uncompressed
+        );
+        assert!(matches!(
+            Compression::detect(&zstd, Compression::Gzip),
+            Err(ParseError::CompressionMismatch { .. })
+        ));
+    }
+
+    #[tokio::test]
+    async fn reads_the_dictionary_id_from_short_reads() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(dir.path());
+        let samples = (0..1000)
+            .map(|i| {
+                format!(
+                    "{{\"match_id\": {}, \"players\": [{}, {}]}}",
+                    i,
+                    i * 7,
+                    i * 13
+                )
+            })
+            .map(String::into_bytes)
+            .collect::<Vec<_>>();
+        let dictionary = zstd::dict::from_samples(&samples, 4096).unwrap();
+        let id = zstd::zstd_safe::get_dict_id_from_dict(&dictionary).unwrap();
+        storage::upload(
+            &store,
+            &dictionary,
+            &dictionaries::get_dictionary_path(id.get()),
+        )
+        .await
+        .unwrap();
+        let compressed = zstd::bulk::Compressor::with_dictionary(0, &dictionary)
+            .unwrap()
+            .compress(&samples[42])
+            .unwrap();
+
+        // Every read returns a single byte
+        let reader = BufReader::with_capacity(1, std::io::Cursor::new(compressed));
+        let decompressed = Compression::Zstd
+            .decompress_stream(&store, reader, 1024)
+            .await
+            .unwrap();
+        assert_eq!(decompressed, samples[42]);
+    }
+}
@@ -0,0 +1,8 @@
+use valveprotos::common::{CDemoFileHeader, CDemoFileInfo};
+
+#[derive(Debug, Clone)]
+pub struct DemoInfo {
+    pub match_id: u64,
+    pub header: CDemoFileHeader,
+    pub file_info: CDemoFileInfo,
+}
@@ -1,9 +1,39 @@
+use log::warn;
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use std::collections::HashSet;
+use std::sync::{LazyLock, Mutex};
 use valveprotos::deadlock::c_msg_match_meta_data_contents::EMatchOutcome;
+use valveprotos::deadlock::c_msg_match_player_damage_matrix::EStatType;
 use valveprotos::deadlock::{
     ECitadelGameMode, ECitadelLobbyTeam, ECitadelMatchMode, ECitadelTeamObjective,
 };
 
+static SEEN_UNKNOWN_VALUES: LazyLock<Mutex<HashSet<(&'static str, i64)>>> =
+    LazyLock::new(|| Mutex::new(HashSet::new()));
+
+/// Counts values we don't know about yet (e.g. after a game update) and warns the first time one
+/// is seen, so the enums can be updated. Values are only logged, as a label would make the metric
+/// grow with every value the game sends.
+pub fn report_unknown_value(enum_name: &'static str, value: i64) {
+    metrics::counter!("db_ingest_unknown_enum_values_total", "enum" => enum_name).increment(1);
+    if SEEN_UNKNOWN_VALUES
+        .lock()
+        .unwrap()
+        .insert((enum_name, value))
+    {
+        warn!("Unknown value {} for enum {}", value, enum_name);
+    }
+}
+
+/// Returns the raw value of a protobuf enum field, reporting it if it's unknown.
+pub fn raw_proto_value<E: TryFrom<i32>>(enum_name: &'static str, value: Option<i32>) -> i32 {
+    let value = value.unwrap_or_default();
+    if E::try_from(value).is_err() {
+        report_unknown_value(enum_name, value as i64);
+    }
+    value
+}
+
 #[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone)]
 #[repr(u8)]
 pub enum GameMode {
@@ -31,7 +61,10 @@ impl From<u8> for GameMode {
             1 => GameMode::Normal,
             2 => GameMode::OnevOneTest,
             3 => GameMode::Sandbox,
-            _ => GameMode::Invalid,
+            _ => {
+                report_unknown_value("GameMode", value as i64);
+                GameMode::Invalid
+            }
         }
     }
 }
@@ -72,7 +105,10 @@ impl From<u8> for MatchMode {
             4 => MatchMode::Ranked,
             5 => MatchMode::ServerTest,
             6 => MatchMode::Tutorial,
-            _ => MatchMode::Invalid,
+            _ => {
+                report_unknown_value("MatchMode", value as i64);
+                MatchMode::Invalid
+            }
         }
     }
 }
@@ -117,7 +153,10 @@ impl From<u8> for Team {
             0 => Team::Team0,
             1 => Team::Team1,
             16 => Team::Spectator,
-            _ => Team::Spectator,
+            _ => {
+                report_unknown_value("Team", value as i64);
+                Team::Spectator
+            }
         }
     }
 }
@@ -190,7 +229,48 @@ impl From<u8> for RegionMode {
             3 => RegionMode::SAmerica,
             4 => RegionMode::Russia,
             5 => RegionMode::Oceania,
-            _ => RegionMode::Row,
+            _ => {
+                report_unknown_value("RegionMode", value as i64);
+                RegionMode::Row
+            }
+        }
+    }
+}
+
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone)]
+#[repr(u8)]
+pub enum ItemEventType {
+    Buy = 0,
+    Sell = 1,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone)]
+#[repr(u8)]
+pub enum StatSource {
+    StatsTypeStat = 0,
+    AbilityStat = 1,
+}
+
+#[derive(Serialize_repr, Deserialize_repr, PartialEq, Debug, Clone)]
+#[repr(i8)]
+pub enum StatType {
+    /// Added to the game after this parser, see `stat_type_raw`
+    Unknown = -1,
+    Damage = 0,
+    Healing = 1,
+    HealPrevented = 2,
+    Mitigated = 3,
+    LethalDamage = 4,
+}
+
+impl From<EStatType> for StatType {
+    fn from(value: EStatType) -> Self {
+        match value {
+            EStatType::KETypeDamage => Self::Damage,
+            EStatType::KETypeHealing => Self::Healing,
+            EStatType::KETypeHealPrevented => Self::HealPrevented,
+            EStatType::KETypeMitigated => Self::Mitigated,
+            EStatType::KETypeLethalDamage => Self::LethalDamage,
         }
     }
 }
@@ -1,15 +1,137 @@
+use crate::models::compression::Compression;
+use ingest_common::file_name::FileNameError;
+use ingest_common::object_store::ObjectStoreError;
+use ingest_common::queue::QueueError;
 use prost::DecodeError;
+use std::fmt::Display;
 use tokio::io;
 
 #[derive(Debug)]
 pub enum ParseError {
-    S3(s3::error::S3Error),
+    Storage(ObjectStoreError),
     Io(io::Error),
-    RmqError(lapin::Error),
+    Queue(QueueError),
     MissingField,
+    /// A field required for the conversion into ClickHouse rows is missing, with its path
+    InvalidField(String),
     FilenameParse,
+    InvalidFileName(FileNameError),
     UnknownVariant,
     ClickhouseError(clickhouse::error::Error),
+    /// The batch holding the rows was dropped before it was inserted
+    BatchDropped,
     Decompress(io::Error),
     ProtobufDecode(DecodeError),
+    InvalidDemoHeader,
+    /// The decompressed file exceeds the maximum size
+    DecompressedTooLarge(u64),
+    /// The magic bytes of the file don't match the compression of its extension
+    CompressionMismatch {
+        extension: Compression,
+        detected: Compression,
+    },
+    /// The file contents do not match its name, with the reason
+    Suspicious(String),
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Storage(e) => write!(f, "storage error: {}", e),
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            Self::Queue(e) => write!(f, "queue error: {}", e),
+            Self::MissingField => write!(f, "missing field"),
+            Self::InvalidField(field) => write!(f, "invalid field: {}", field),
+            Self::FilenameParse => write!(f, "invalid file path"),
+            Self::InvalidFileName(e) => write!(f, "invalid file name: {}", e),
+            Self::UnknownVariant => write!(f, "unknown file type or compression"),
+            Self::ClickhouseError(e) => write!(f, "ClickHouse error: {}", e),
+            Self::BatchDropped => write!(f, "batch dropped before it was inserted"),
+            Self::Decompress(e) => write!(f, "decompression error: {}", e),
+            Self::ProtobufDecode(e) => write!(f, "protobuf decode error: {}", e),
+            Self::InvalidDemoHeader => write!(f, "invalid demo header"),
+            Self::DecompressedTooLarge(max_size) => {
+                write!(f, "decompressed file exceeds {} bytes", max_size)
+            }
+            Self::CompressionMismatch {
+                extension,
+                detected,
+            } => write!(
+                f,
+                "extension claims {:?} compression, but the file is {:?}",
+                extension, detected
+            ),
+            Self::Suspicious(reason) => write!(f, "suspicious file: {}", reason),
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+/// ClickHouse error codes of overloaded or unavailable servers, other codes are caused by the
+/// inserted data or query.
+const TRANSIENT_CLICKHOUSE_CODES: [u32; 10] = [159, 202, 203, 209, 210, 236, 241, 242, 252, 394];
+
+impl ParseError {
+    /// Errors caused by external services, which might succeed when retried later.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::Io(_) | Self::Queue(_) | Self::BatchDropped => true,
+            Self::Storage(e) => e.is_transient(),
+            Self::ClickhouseError(e) => is_transient_clickhouse_error(e),
+            _ => false,
+        }
+    }
+}
+
+pub fn is_transient_clickhouse_error(e: &clickhouse::error::Error) -> bool {
+    match e {
+        clickhouse::error::Error::Network(_) | clickhouse::error::Error::TimedOut => true,
+        // Bad responses start with the error code, e.g. `Code: 252. DB::Exception: ...`, or are
+        // the HTTP status if the server did not answer
+        clickhouse::error::Error::BadResponse(response) => response
+            .strip_prefix("Code: ")
+            .and_then(|r| r.split('.').next())
+            .and_then(|code| code.parse::<u32>().ok())
+            .is_none_or(|code| TRANSIENT_CLICKHOUSE_CODES.contains(&code)),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clickhouse::error::Error;
+
+    #[test]
+    fn classifies_clickhouse_errors() {
+        assert!(is_transient_clickhouse_error(&Error::TimedOut));
+        assert!(is_transient_clickhouse_error(&Error::BadResponse(
+            "Code: 252. DB::Exception: Too many parts".to_string()
+        )));
+        assert!(is_transient_clickhouse_error(&Error::BadResponse(
+            "503 Service Unavailable".to_string()
+        )));
+        assert!(!is_transient_clickhouse_error(&Error::BadResponse(
+            "Code: 27. DB::Exception: Cannot parse input".to_string()
+        )));
+        assert!(!is_transient_clickhouse_error(&Error::Custom(
+            "invalid row".to_string()
+        )));
+    }
+
+    #[test]
+    fn data_errors_are_permanent() {
+        let error = ParseError::ClickhouseError(Error::BadResponse(
+            "Code: 53. DB::Exception: Type mismatch".to_string(),
+        ));
+        assert!(!error.is_transient());
+        assert!(ParseError::BatchDropped.is_transient());
+    }
+
+    #[test]
+    fn missing_objects_are_permanent() {
+        let error = ParseError::Storage(ObjectStoreError::NotFound("a".to_string()));
+        assert!(!error.is_transient());
+    }
 }
@@ -1,9 +1,9 @@
 use crate::models::compression::Compression;
 use crate::models::error::ParseError;
-use crate::models::file_keyword::FileKeyword;
 use crate::models::file_type::FileType;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use crate::models::version::PARSER_VERSION;
+use ingest_common::file_name::IngestFileName;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 #[derive(Debug, Clone)]
@@ -12,42 +12,46 @@ pub struct FileData {
     pub file_path: PathBuf,
     pub file_type: FileType,
     pub compression: Compression,
-    pub kwargs: HashMap<FileKeyword, String>,
+    pub name: IngestFileName,
 }
 
-impl TryFrom<&PathBuf> for FileData {
-    type Error = ParseError;
-
-    fn try_from(file_path: &PathBuf) -> Result<Self, Self::Error> {
+impl FileData {
+    /// Parses the name of the file, looking up the file type of its extension with `file_type`.
+    pub fn parse(
+        file_path: &Path,
+        file_type: impl FnOnce(&str) -> Option<FileType>,
+    ) -> Result<Self, ParseError> {
         let filename = file_path
             .file_name()
             .ok_or(ParseError::FilenameParse)?
             .to_str()
             .ok_or(ParseError::FilenameParse)?;
-        let parts: Vec<&str> = filename.split('.').collect();
-        let (filename, ext, compression) = match parts.as_slice() {
-            [filename, ext, compression] => (filename, ext, Compression::from_str(compression)?),
-            [filename, ext] => (filename, ext, Compression::default()),
-            _ => return Err(ParseError::FilenameParse),
-        };
-        let file_type = FileType::from_str(ext)?;
-        let file_name_parts = filename.split('_').collect::<Vec<&str>>();
-        let file_name_data: HashMap<FileKeyword, String> = file_name_parts
-            .into_iter()
-            .filter_map(|part| {
-                let (key, value) = part.split_at(1);
-                FileKeyword::from_str(key)
-                    .ok()
-                    .map(|k| (k, value.to_string()))
-            })
-            .collect();
+        let name = IngestFileName::from_str(filename).map_err(ParseError::InvalidFileName)?;
+        let file_type = file_type(&name.extension).ok_or(ParseError::UnknownVariant)?;
+        let compression = name
+            .compression
+            .as_deref()
+            .map(Compression::from_str)
+            .transpose()?
+            .unwrap_or_default();
 
         Ok(Self {
-            file_path: file_path.clone(),
-            file_name: filename.to_string(),
+            file_path: file_path.to_path_buf(),
+            file_name: name.stem.to_string(),
             file_type,
             compression,
-            kwargs: file_name_data,
+            name,
         })
     }
+
+    /// Identifies the file across retries, used to deduplicate inserts. Includes the parser
+    /// version, so reprocessing with a newer parser inserts the new rows.
+    pub fn dedup_token(&self) -> String {
+        format!(
+            "{}.{}.v{}",
+            self.file_name,
+            self.file_type.extension(),
+            PARSER_VERSION
+        )
+    }
 }
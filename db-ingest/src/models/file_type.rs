@@ -1,44 +1,29 @@
-use crate::models::error::ParseError;
 use std::fmt::Display;
-use std::str::FromStr;
 use std::write;
 
-#[derive(Debug, Clone, Copy)]
-pub enum FileType {
-    Metadata,
-    MetadataContent,
-    ActiveMatchesJsonLines,
+/// A file format, declared by the parser of the format.
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub struct FileType {
+    extension: &'static str,
+    /// Directory of the files in `/parsed/` and `/failed/`
+    directory: &'static str,
 }
 
-impl FromStr for FileType {
-    type Err = ParseError;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "meta" => Ok(Self::Metadata),
-            "metac" => Ok(Self::MetadataContent),
-            "amjsonl" => Ok(Self::ActiveMatchesJsonLines),
-            _ => Err(ParseError::UnknownVariant),
+impl FileType {
+    pub const fn new(extension: &'static str, directory: &'static str) -> Self {
+        Self {
+            extension,
+            directory,
         }
     }
-}
 
-impl Display for FileType {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        match self {
-            Self::Metadata => write!(f, "meta"),
-            Self::MetadataContent => write!(f, "metac"),
-            Self::ActiveMatchesJsonLines => write!(f, "active-matches"),
-        }
+    pub fn extension(&self) -> &'static str {
+        self.extension
     }
 }
 
-impl FileType {
-    pub fn extension(&self) -> &'static str {
-        match self {
-            Self::Metadata => "meta",
-            Self::MetadataContent => "metac",
-            Self::ActiveMatchesJsonLines => "amjsonl",
-        }
+impl Display for FileType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.directory)
     }
 }
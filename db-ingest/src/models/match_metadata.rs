@@ -0,0 +1,9 @@
+use valveprotos::deadlock::c_msg_match_meta_data_contents::MatchInfo;
+
+/// Contents of a `.meta` file, the `CMsgMatchMetaData` envelope with its decoded match details.
+#[derive(Debug, Clone)]
+pub struct MatchMetadata {
+    pub version: Option<u32>,
+    pub match_id: Option<u64>,
+    pub match_info: MatchInfo,
+}
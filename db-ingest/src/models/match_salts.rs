@@ -0,0 +1,9 @@
+use clickhouse::Row;
+use serde::Deserialize;
+
+#[derive(Row, Debug, Deserialize)]
+pub struct MatchSalts {
+    pub cluster_id: u32,
+    pub metadata_salt: Option<u32>,
+    pub replay_salt: Option<u32>,
+}
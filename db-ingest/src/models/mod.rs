@@ -1,10 +1,20 @@
 pub mod active_match;
 pub mod clickhouse_active_match;
+pub mod clickhouse_match_damage;
+pub mod clickhouse_match_demo;
+pub mod clickhouse_match_item;
+pub mod clickhouse_match_kill;
 pub mod clickhouse_match_metadata;
+pub mod clickhouse_match_metadata_envelope;
+pub mod clickhouse_match_player_stat;
 pub mod compression;
+pub mod demo_info;
 pub mod enums;
 pub mod error;
 pub mod file_data;
-pub mod file_keyword;
 pub mod file_type;
+pub mod match_metadata;
+pub mod match_salts;
 pub mod parse_result;
+pub mod stat_names;
+pub mod version;
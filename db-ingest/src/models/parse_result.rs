@@ -1,10 +1,35 @@
-use crate::models::compression::Compression;
-use crate::models::file_type::FileType;
-
 #[derive(Debug)]
 pub struct ParseResult<T> {
-    pub file_type: FileType,
-    pub compression: Compression,
-    pub data: Vec<u8>,
+    /// Data to store in `/parsed/`, `None` if that is the input file unchanged
+    pub data: Option<Vec<u8>>,
     pub parsed_data: T,
+    /// Match ids found in the file contents with the field they were read from, these are
+    /// validated against the file name
+    pub match_ids: Vec<(&'static str, u64)>,
+}
+
+impl<T> ParseResult<T> {
+    pub fn new(data: Vec<u8>, parsed_data: T) -> Self {
+        Self {
+            data: Some(data),
+            parsed_data,
+            match_ids: vec![],
+        }
+    }
+
+    /// The input file is stored in `/parsed/` as it is, so it doesn't have to be copied.
+    pub fn unchanged(parsed_data: T) -> Self {
+        Self {
+            data: None,
+            parsed_data,
+            match_ids: vec![],
+        }
+    }
+
+    pub fn with_match_id(mut self, field: &'static str, match_id: Option<u64>) -> Self {
+        if let Some(match_id) = match_id {
+            self.match_ids.push((field, match_id));
+        }
+        self
+    }
 }
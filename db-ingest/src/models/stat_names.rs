@@ -0,0 +1,107 @@
+/// Names of the `stats_type_stat` indices and of the ability stats, which change with game
+/// patches. Mappings are never edited once released, a patch adds a new mapping instead, so every
+/// row stays interpretable through the `mapping_version` stored with it.
+pub struct StatNameMapping {
+    pub version: u32,
+    /// Matches starting at or after this unix timestamp use the mapping
+    pub valid_from: u32,
+    /// Named like `CMsgMatchPlayerDamageMatrix.EStatType`, the stat type enum of the match
+    /// metadata, which the indices follow. `CMsgCitadelProfileCard.EStatID` numbers the stats of
+    /// profile card slots instead.
+    pub stats_type_stat: &'static [&'static str],
+    /// Names of ability stats by ability id. The protos have no enum of ability ids, so abilities
+    /// are only named once their id is known from the game files.
+    pub ability_stats: &'static [(u32, &'static str)],
+}
+
+/// Sorted by `valid_from`. Indices and abilities without a known name are labelled by their index
+/// or ability id.
+pub const STAT_NAME_MAPPINGS: &[StatNameMapping] = &[StatNameMapping {
+    version: 1,
+    valid_from: 0,
+    stats_type_stat: &[
+        "damage",
+        "healing",
+        "heal_prevented",
+        "mitigated",
+        "lethal_damage",
+    ],
+    ability_stats: &[],
+}];
+
+impl StatNameMapping {
+    pub fn for_match(start_time: u32) -> &'static Self {
+        STAT_NAME_MAPPINGS
+            .iter()
+            .rev()
+            .find(|m| m.valid_from <= start_time)
+            .unwrap_or(&STAT_NAME_MAPPINGS[0])
+    }
+
+    pub fn stats_type_stat_name(&self, index: usize) -> String {
+        self.stats_type_stat
+            .get(index)
+            .map(|name| name.to_string())
+            .unwrap_or_else(|| format!("stats_type_stat_{}", index))
+    }
+
+    pub fn ability_stat_name(&self, ability_id: u32) -> String {
+        self.ability_stats
+            .iter()
+            .find(|(id, _)| *id == ability_id)
+            .map(|(_, name)| name.to_string())
+            .unwrap_or_else(|| format!("ability_stat_{}", ability_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use valveprotos::deadlock::c_msg_match_player_damage_matrix::EStatType;
+
+    /// `k_eType_HealPrevented` is named `heal_prevented`
+    fn stat_type_name(stat_type: EStatType) -> String {
+        let name = stat_type.as_str_name().trim_start_matches("k_eType_");
+        let mut snake_case = String::new();
+        for (i, c) in name.chars().enumerate() {
+            if c.is_uppercase() && i > 0 {
+                snake_case.push('_');
+            }
+            snake_case.push(c.to_ascii_lowercase());
+        }
+        snake_case
+    }
+
+    #[test]
+    fn stats_type_stat_names_match_the_stat_types() {
+        for mapping in STAT_NAME_MAPPINGS {
+            for (index, name) in mapping.stats_type_stat.iter().enumerate() {
+                let stat_type = EStatType::try_from(index as i32).unwrap();
+                assert_eq!(
+                    stat_type_name(stat_type),
+                    *name,
+                    "mapping {}",
+                    mapping.version
+                );
+            }
+        }
+        // The latest mapping names every stat type
+        let latest = STAT_NAME_MAPPINGS.last().unwrap();
+        assert!(EStatType::try_from(latest.stats_type_stat.len() as i32).is_err());
+    }
+
+    #[test]
+    fn mappings_are_sorted() {
+        assert!(STAT_NAME_MAPPINGS
+            .windows(2)
+            .all(|m| m[0].valid_from <= m[1].valid_from && m[0].version < m[1].version));
+    }
+
+    #[test]
+    fn unknown_stats_are_labelled_by_index() {
+        let mapping = StatNameMapping::for_match(0);
+        assert_eq!(mapping.stats_type_stat_name(2), "heal_prevented");
+        assert_eq!(mapping.stats_type_stat_name(40), "stats_type_stat_40");
+        assert_eq!(mapping.ability_stat_name(123), "ability_stat_123");
+    }
+}
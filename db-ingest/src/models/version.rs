@@ -0,0 +1,12 @@
+use std::time::SystemTime;
+
+/// Version of the conversion into ClickHouse rows, bump it whenever the produced rows change.
+pub const PARSER_VERSION: u32 = 7;
+
+/// Rows ingested later replace earlier ones, so the current time is used as the version.
+pub fn ingestion_version() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
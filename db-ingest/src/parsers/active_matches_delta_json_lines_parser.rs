@@ -0,0 +1,118 @@
+use crate::models::active_match::ActiveMatch;
+use crate::models::compression::Compression;
+use crate::models::error::ParseError;
+use crate::models::file_data::FileData;
+use crate::models::file_type::FileType;
+use crate::models::parse_result::ParseResult;
+use crate::parsers::parser::Parser;
+use ingest_common::snapshot_delta::Line;
+use log::{debug, warn};
+
+pub const ACTIVE_MATCHES_DELTA: FileType = FileType::new("amdjsonl", "active-matches-delta");
+
+#[derive(Default, Debug)]
+pub struct ActiveMatchesDeltaJsonLinesParser;
+
+impl Parser for ActiveMatchesDeltaJsonLinesParser {
+    type Output = Vec<ActiveMatch>;
+
+    const FILE_TYPES: &'static [FileType] = &[ACTIVE_MATCHES_DELTA];
+    const OUTPUT_FILE_TYPE: FileType = ACTIVE_MATCHES_DELTA;
+    const COMPRESSION: Compression = Compression::Zstd;
+
+    fn parse(
+        &self,
+        file_data: &FileData,
+        data: &[u8],
+    ) -> Result<ParseResult<Vec<ActiveMatch>>, ParseError> {
+        let data_str = String::from_utf8_lossy(data);
+        let mut parsed_data = vec![];
+        for (i, line) in data_str.lines().enumerate() {
+            let snapshots =
+                serde_json::from_str::<Line<ActiveMatch>>(line).and_then(Line::into_snapshots);
+            match snapshots {
+                Ok(snapshots) => parsed_data.extend(snapshots),
+                Err(e) => {
+                    warn!(
+                        "Skipping invalid line {} of {}: {}",
+                        i + 1,
+                        file_data.file_name,
+                        e
+                    );
+                    metrics::counter!("db_ingest_invalid_active_matches_lines_total").increment(1);
+                }
+            }
+        }
+        debug!("Active Matches: {:#?}", parsed_data.len());
+        Ok(ParseResult::unchanged(parsed_data))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::clickhouse_active_match::ClickHouseActiveMatch;
+    use crate::parsers::active_matches_json_lines_parser::ActiveMatchesJsonLinesParser;
+    use crate::parsers::registry::PARSERS;
+    use ingest_common::{snapshot_delta, testing};
+    use serde_json::{json, Value};
+    use std::path::Path;
+
+    /// The snapshots of a match as written by the scraper, which skips unset flags.
+    fn snapshots(match_id: u32, history_incomplete: bool) -> Vec<Value> {
+        (0..3)
+            .map(|i| {
+                let mut snapshot = testing::active_match(match_id, 100 + 21 * i);
+                snapshot["players"][1]["abandoned"] = json!(i == 2);
+                snapshot["spectators"] = json!(i);
+                snapshot["net_worth_team_0"] = json!(1000 * i);
+                snapshot["net_worth_team_1"] = json!(900 * i);
+                if history_incomplete {
+                    snapshot["history_incomplete"] = json!(true);
+                }
+                snapshot
+            })
+            .collect()
+    }
+
+    /// The rows without their ingestion version, which is the current time.
+    fn rows(active_matches: Vec<ActiveMatch>) -> Vec<Value> {
+        active_matches
+            .into_iter()
+            .map(|am| {
+                let mut row = serde_json::to_value(ClickHouseActiveMatch::from(am)).unwrap();
+                row.as_object_mut().unwrap().remove("ingestion_version");
+                row
+            })
+            .collect()
+    }
+
+    #[test]
+    fn produces_the_same_rows_as_full_snapshots() {
+        let matches = [snapshots(1, false), snapshots(2, true)];
+        let full = matches
+            .iter()
+            .map(|s| serde_json::to_string(s).unwrap() + "\n")
+            .collect::<String>();
+        let delta = matches
+            .iter()
+            .map(|s| serde_json::to_string(&snapshot_delta::encode(s).unwrap()).unwrap() + "\n")
+            .collect::<String>();
+        assert!(delta.len() < full.len());
+        // Truncated lines are skipped
+        let delta = delta + "{\"header\": {\"match_id\"\n";
+
+        let full_file = PARSERS.file_data(Path::new("100-142.amjsonl")).unwrap();
+        let delta_file = PARSERS.file_data(Path::new("100-142.amdjsonl")).unwrap();
+        let full_rows = ActiveMatchesJsonLinesParser
+            .parse(&full_file, full.as_bytes())
+            .unwrap()
+            .parsed_data;
+        let delta_rows = ActiveMatchesDeltaJsonLinesParser
+            .parse(&delta_file, delta.as_bytes())
+            .unwrap()
+            .parsed_data;
+        assert_eq!(full_rows.len(), 6);
+        assert_eq!(rows(delta_rows), rows(full_rows));
+    }
+}
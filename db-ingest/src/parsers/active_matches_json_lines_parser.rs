@@ -5,26 +5,32 @@ use crate::models::file_data::FileData;
 use crate::models::file_type::FileType;
 use crate::models::parse_result::ParseResult;
 use crate::parsers::parser::Parser;
+use log::debug;
+
+pub const ACTIVE_MATCHES: FileType = FileType::new("amjsonl", "active-matches");
 
 #[derive(Default, Debug)]
 pub struct ActiveMatchesJsonLinesParser;
 
-impl Parser<Vec<Vec<ActiveMatch>>> for ActiveMatchesJsonLinesParser {
+impl Parser for ActiveMatchesJsonLinesParser {
+    type Output = Vec<ActiveMatch>;
+
+    const FILE_TYPES: &'static [FileType] = &[ACTIVE_MATCHES];
+    const OUTPUT_FILE_TYPE: FileType = ACTIVE_MATCHES;
+    const COMPRESSION: Compression = Compression::Zstd;
+
     fn parse(
         &self,
         _: &FileData,
         data: &[u8],
-    ) -> Result<ParseResult<Vec<Vec<ActiveMatch>>>, ParseError> {
+    ) -> Result<ParseResult<Vec<ActiveMatch>>, ParseError> {
         let data_str = String::from_utf8_lossy(data);
         let parsed_data = data_str
             .lines()
             .filter_map(|l| serde_json::from_str::<Vec<ActiveMatch>>(l).ok())
+            .flatten()
             .collect::<Vec<_>>();
-        Ok(ParseResult {
-            file_type: FileType::ActiveMatchesJsonLines,
-            compression: Compression::Zstd,
-            data: data.to_vec(),
-            parsed_data,
-        })
+        debug!("Active Matches: {:#?}", parsed_data.len());
+        Ok(ParseResult::unchanged(parsed_data))
     }
 }
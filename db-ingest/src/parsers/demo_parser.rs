@@ -0,0 +1,153 @@
+use crate::models::compression::Compression;
+use crate::models::demo_info::DemoInfo;
+use crate::models::error::ParseError;
+use crate::models::file_data::FileData;
+use crate::models::file_type::FileType;
+use crate::models::parse_result::ParseResult;
+use crate::parsers::parser::Parser;
+use prost::Message;
+use valveprotos::common::{CDemoFileHeader, CDemoFileInfo, EDemoCommands};
+
+const DEMO_FILE_STAMP: &[u8; 8] = b"PBDEMS2\0";
+const DEMO_HEADER_SIZE: usize = 16;
+
+pub const DEMO: FileType = FileType::new("dem", "dem");
+
+#[derive(Default, Debug)]
+pub struct DemoParser;
+
+impl Parser for DemoParser {
+    type Output = DemoInfo;
+
+    const FILE_TYPES: &'static [FileType] = &[DEMO];
+    const OUTPUT_FILE_TYPE: FileType = DEMO;
+    const COMPRESSION: Compression = Compression::Zstd;
+
+    fn parse(
+        &self,
+        file_data: &FileData,
+        data: &[u8],
+    ) -> Result<ParseResult<DemoInfo>, ParseError> {
+        if data.len() < DEMO_HEADER_SIZE || &data[..8] != DEMO_FILE_STAMP {
+            return Err(ParseError::InvalidDemoHeader);
+        }
+        let file_info_offset = i32::from_le_bytes(data[8..12].try_into().unwrap());
+        let file_info_offset =
+            usize::try_from(file_info_offset).map_err(|_| ParseError::InvalidDemoHeader)?;
+
+        // The first command of every demo is the file header
+        let (command, header) = read_command(&data[DEMO_HEADER_SIZE..])?;
+        if command != EDemoCommands::DemFileHeader {
+            return Err(ParseError::InvalidDemoHeader);
+        }
+        let header =
+            CDemoFileHeader::decode(header.as_slice()).map_err(ParseError::ProtobufDecode)?;
+
+        // The file info is written at the end of the demo, the offset is stored in the header
+        let (command, file_info) = read_command(
+            data.get(file_info_offset..)
+                .ok_or(ParseError::InvalidDemoHeader)?,
+        )?;
+        if command != EDemoCommands::DemFileInfo {
+            return Err(ParseError::InvalidDemoHeader);
+        }
+        let file_info =
+            CDemoFileInfo::decode(file_info.as_slice()).map_err(ParseError::ProtobufDecode)?;
+
+        // Deadlock demos don't contain their match id, the `dota` game info is never populated
+        let match_id = file_data.name.match_id().ok_or(ParseError::MissingField)?;
+
+        let demo_info = DemoInfo {
+            match_id,
+            header,
+            file_info,
+        };
+        Ok(ParseResult::unchanged(demo_info))
+    }
+}
+
+/// Reads a single demo command, returning its type and the (decompressed) message payload.
+fn read_command(mut data: &[u8]) -> Result<(EDemoCommands, Vec<u8>), ParseError> {
+    let command = prost::encoding::decode_varint(&mut data).map_err(ParseError::ProtobufDecode)?;
+    let _tick = prost::encoding::decode_varint(&mut data).map_err(ParseError::ProtobufDecode)?;
+    let size = prost::encoding::decode_varint(&mut data).map_err(ParseError::ProtobufDecode)?;
+    let payload = usize::try_from(size)
+        .ok()
+        .and_then(|size| data.get(..size))
+        .ok_or(ParseError::InvalidDemoHeader)?;
+
+    let is_compressed = command & EDemoCommands::DemIsCompressed as u64 != 0;
+    let command = command & !(EDemoCommands::DemIsCompressed as u64);
+    let command = i32::try_from(command)
+        .ok()
+        .and_then(|c| EDemoCommands::try_from(c).ok())
+        .ok_or(ParseError::InvalidDemoHeader)?;
+    let payload = if is_compressed {
+        snap::raw::Decoder::new()
+            .decompress_vec(payload)
+            .map_err(|e| ParseError::Decompress(e.into()))?
+    } else {
+        payload.to_vec()
+    };
+    Ok((command, payload))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::registry::PARSERS;
+    use std::path::Path;
+
+    fn command(command: EDemoCommands, payload: &[u8]) -> Vec<u8> {
+        let mut data = vec![];
+        prost::encoding::encode_varint(command as u64, &mut data);
+        prost::encoding::encode_varint(0, &mut data);
+        prost::encoding::encode_varint(payload.len() as u64, &mut data);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    #[test]
+    fn reads_the_match_id_from_the_file_name() {
+        let header = CDemoFileHeader {
+            map_name: Some("dl_midtown".to_string()),
+            ..Default::default()
+        };
+        let file_info = CDemoFileInfo {
+            playback_ticks: Some(1000),
+            ..Default::default()
+        };
+        let mut data = DEMO_FILE_STAMP.to_vec();
+        data.extend_from_slice(&[0; 8]);
+        data.extend(command(
+            EDemoCommands::DemFileHeader,
+            &header.encode_to_vec(),
+        ));
+        let file_info_offset = data.len() as i32;
+        data[8..12].copy_from_slice(&file_info_offset.to_le_bytes());
+        data.extend(command(
+            EDemoCommands::DemFileInfo,
+            &file_info.encode_to_vec(),
+        ));
+
+        let file_data = PARSERS
+            .file_data(Path::new("T001_M31452_C1_S2.dem"))
+            .unwrap();
+        let result = DemoParser.parse(&file_data, &data).unwrap();
+        assert!(result.data.is_none());
+        assert_eq!(result.parsed_data.match_id, 31452);
+        assert_eq!(result.parsed_data.header, header);
+        assert_eq!(result.parsed_data.file_info, file_info);
+    }
+
+    #[test]
+    fn rejects_files_without_the_demo_stamp() {
+        let file_data = PARSERS
+            .file_data(Path::new("T001_M31452_C1_S2.dem"))
+            .unwrap();
+        assert!(matches!(
+            DemoParser.parse(&file_data, &[0; 32]),
+            Err(ParseError::InvalidDemoHeader)
+        ));
+    }
+}
@@ -8,20 +8,24 @@ use prost::Message;
 use valveprotos::deadlock::c_msg_match_meta_data_contents::MatchInfo;
 use valveprotos::deadlock::CMsgMatchMetaDataContents;
 
+pub const METADATA_CONTENT: FileType = FileType::new("metac", "metac");
+
 #[derive(Default, Debug)]
 pub struct MetaDataContentParser;
 
-impl Parser<MatchInfo> for MetaDataContentParser {
+impl Parser for MetaDataContentParser {
+    type Output = MatchInfo;
+
+    const FILE_TYPES: &'static [FileType] = &[METADATA_CONTENT];
+    const OUTPUT_FILE_TYPE: FileType = METADATA_CONTENT;
+    const COMPRESSION: Compression = Compression::Zstd;
+
     fn parse(&self, _: &FileData, data: &[u8]) -> Result<ParseResult<MatchInfo>, ParseError> {
         let parsed_data = CMsgMatchMetaDataContents::decode(data)
             .map_err(ParseError::ProtobufDecode)?
             .match_info
             .ok_or(ParseError::MissingField)?;
-        Ok(ParseResult {
-            file_type: FileType::MetadataContent,
-            data: data.to_vec(),
-            compression: Compression::Zstd,
-            parsed_data,
-        })
+        let match_id = parsed_data.match_id;
+        Ok(ParseResult::unchanged(parsed_data).with_match_id("MatchInfo.match_id", match_id))
     }
 }
@@ -1,3 +1,4 @@
+use crate::parsers::metadata_content_parser::METADATA_CONTENT;
 use crate::parsers::parser::Parser;
 use prost::Message;
 
@@ -5,28 +6,40 @@ use crate::models::compression::Compression;
 use crate::models::error::ParseError;
 use crate::models::file_data::FileData;
 use crate::models::file_type::FileType;
+use crate::models::match_metadata::MatchMetadata;
 use crate::models::parse_result::ParseResult;
-use valveprotos::deadlock::c_msg_match_meta_data_contents::MatchInfo;
 use valveprotos::deadlock::{CMsgMatchMetaData, CMsgMatchMetaDataContents};
 
+pub const METADATA: FileType = FileType::new("meta", "meta");
+
 #[derive(Default, Debug)]
 pub struct MetaDataParser;
 
-impl Parser<MatchInfo> for MetaDataParser {
-    fn parse(&self, _: &FileData, data: &[u8]) -> Result<ParseResult<MatchInfo>, ParseError> {
+impl Parser for MetaDataParser {
+    type Output = MatchMetadata;
+
+    const FILE_TYPES: &'static [FileType] = &[METADATA];
+    const OUTPUT_FILE_TYPE: FileType = METADATA_CONTENT;
+    const COMPRESSION: Compression = Compression::Zstd;
+    const KEEP_ORIGINAL: bool = true;
+
+    fn parse(&self, _: &FileData, data: &[u8]) -> Result<ParseResult<MatchMetadata>, ParseError> {
         // Check if match metadata is parseable
         let match_metadata = CMsgMatchMetaData::decode(data).map_err(ParseError::ProtobufDecode)?;
         let match_details = match_metadata.match_details();
         // Check if match metadata is parseable
-        let parsed_data = CMsgMatchMetaDataContents::decode(match_details)
+        let match_info = CMsgMatchMetaDataContents::decode(match_details)
             .map_err(ParseError::ProtobufDecode)?
             .match_info
             .ok_or(ParseError::MissingField)?;
-        Ok(ParseResult {
-            file_type: FileType::MetadataContent,
-            data: match_details.to_vec(),
-            compression: Compression::Zstd,
-            parsed_data,
-        })
+        let parsed_data = MatchMetadata {
+            version: match_metadata.version,
+            match_id: match_metadata.match_id,
+            match_info,
+        };
+        let match_id = parsed_data.match_info.match_id;
+        Ok(ParseResult::new(match_details.to_vec(), parsed_data)
+            .with_match_id("CMsgMatchMetaData.match_id", match_metadata.match_id)
+            .with_match_id("MatchInfo.match_id", match_id))
     }
 }
@@ -1,4 +1,7 @@
+pub(crate) mod active_matches_delta_json_lines_parser;
 pub(crate) mod active_matches_json_lines_parser;
+pub(crate) mod demo_parser;
 pub(crate) mod metadata_content_parser;
 pub(crate) mod metadata_parser;
 pub(crate) mod parser;
+pub(crate) mod registry;
@@ -1,7 +1,24 @@
+use crate::models::compression::Compression;
 use crate::models::error::ParseError;
 use crate::models::file_data::FileData;
+use crate::models::file_type::FileType;
 use crate::models::parse_result::ParseResult;
 
-pub trait Parser<T>: Send {
-    fn parse(&self, file_data: &FileData, data: &[u8]) -> Result<ParseResult<T>, ParseError>;
+pub trait Parser: Send + Sync {
+    type Output: Send + Sync;
+
+    /// File types this parser is responsible for
+    const FILE_TYPES: &'static [FileType];
+    /// File type the parsed data is stored as in `/parsed/`
+    const OUTPUT_FILE_TYPE: FileType;
+    /// Compression the parsed data is stored with in `/parsed/`
+    const COMPRESSION: Compression;
+    /// Whether the input file is stored in `/parsed/` as well, next to the parsed data
+    const KEEP_ORIGINAL: bool = false;
+
+    fn parse(
+        &self,
+        file_data: &FileData,
+        data: &[u8],
+    ) -> Result<ParseResult<Self::Output>, ParseError>;
 }
@@ -0,0 +1,147 @@
+use crate::ingestors::clickhouse_ingestor::ClickhouseIngestor;
+use crate::ingestors::ingestor::{Ingestor, MatchSaltsSource};
+use crate::models::compression::Compression;
+use crate::models::error::ParseError;
+use crate::models::file_data::FileData;
+use crate::models::file_type::FileType;
+use crate::parsers::active_matches_delta_json_lines_parser::ActiveMatchesDeltaJsonLinesParser;
+use crate::parsers::active_matches_json_lines_parser::ActiveMatchesJsonLinesParser;
+use crate::parsers::demo_parser::DemoParser;
+use crate::parsers::metadata_content_parser::MetaDataContentParser;
+use crate::parsers::metadata_parser::MetaDataParser;
+use crate::parsers::parser::Parser;
+use crate::validation;
+use std::collections::HashMap;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, LazyLock};
+
+/// Adding a file format only takes a [`Parser`] registered here, its file types are known by
+/// their extension from then on.
+pub static PARSERS: LazyLock<ParserRegistry<ClickhouseIngestor>> = LazyLock::new(|| {
+    let mut registry = ParserRegistry::default();
+    registry.register(MetaDataParser);
+    registry.register(MetaDataContentParser);
+    registry.register(ActiveMatchesJsonLinesParser);
+    registry.register(ActiveMatchesDeltaJsonLinesParser);
+    registry.register(DemoParser);
+    registry
+});
+
+/// Data of a parsed file, which should be stored in `/parsed/`
+pub struct ParsedFile {
+    pub file_type: FileType,
+    pub compression: Compression,
+    /// `None` if the input file is stored unchanged
+    pub data: Option<Vec<u8>>,
+    pub keep_original: bool,
+}
+
+type ParseFuture<'a> = Pin<Box<dyn Future<Output = Result<ParsedFile, ParseError>> + Send + 'a>>;
+
+/// Type erased [`Parser`], which parses a file and ingests the parsed data into the sink `I`.
+pub trait FileParser<I>: Send + Sync {
+    fn parse_and_ingest<'a>(
+        &'a self,
+        ingestor: &'a I,
+        file_data: &'a FileData,
+        data: &'a [u8],
+    ) -> ParseFuture<'a>;
+}
+
+impl<P, I> FileParser<I> for P
+where
+    P: Parser,
+    I: Ingestor<P::Output> + MatchSaltsSource,
+{
+    fn parse_and_ingest<'a>(
+        &'a self,
+        ingestor: &'a I,
+        file_data: &'a FileData,
+        data: &'a [u8],
+    ) -> ParseFuture<'a> {
+        Box::pin(async move {
+            let result = self.parse(file_data, data)?;
+            validation::validate(ingestor, file_data, &result.match_ids).await?;
+            ingestor
+                .ingest(&result.parsed_data, &file_data.dedup_token())
+                .await?;
+            Ok(ParsedFile {
+                file_type: P::OUTPUT_FILE_TYPE,
+                compression: P::COMPRESSION,
+                data: result.data,
+                keep_original: P::KEEP_ORIGINAL,
+            })
+        })
+    }
+}
+
+/// Parsers by the extension of the file types they read, ingesting into the sink `I`.
+pub struct ParserRegistry<I> {
+    parsers: HashMap<&'static str, Arc<dyn FileParser<I>>>,
+    /// Read and written file types by their extension
+    file_types: HashMap<&'static str, FileType>,
+}
+
+impl<I> Default for ParserRegistry<I> {
+    fn default() -> Self {
+        Self {
+            parsers: HashMap::new(),
+            file_types: HashMap::new(),
+        }
+    }
+}
+
+impl<I: MatchSaltsSource + 'static> ParserRegistry<I> {
+    pub fn register<P>(&mut self, parser: P)
+    where
+        P: Parser + 'static,
+        I: Ingestor<P::Output>,
+    {
+        let parser: Arc<dyn FileParser<I>> = Arc::new(parser);
+        for file_type in P::FILE_TYPES {
+            self.parsers.insert(file_type.extension(), parser.clone());
+            self.file_types.insert(file_type.extension(), *file_type);
+        }
+        self.file_types
+            .insert(P::OUTPUT_FILE_TYPE.extension(), P::OUTPUT_FILE_TYPE);
+    }
+}
+
+impl<I> ParserRegistry<I> {
+    pub fn get(&self, file_type: FileType) -> Option<&dyn FileParser<I>> {
+        self.parsers.get(file_type.extension()).map(|p| p.as_ref())
+    }
+
+    pub fn file_type(&self, extension: &str) -> Option<FileType> {
+        self.file_types.get(extension).copied()
+    }
+
+    /// Parses the name of a file of one of the registered file types.
+    pub fn file_data(&self, file_path: &Path) -> Result<FileData, ParseError> {
+        FileData::parse(file_path, |extension| self.file_type(extension))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_types_are_known_by_their_extension() {
+        for (extension, directory) in [
+            ("meta", "meta"),
+            ("metac", "metac"),
+            ("amjsonl", "active-matches"),
+            ("amdjsonl", "active-matches-delta"),
+            ("dem", "dem"),
+        ] {
+            let file_type = PARSERS.file_type(extension).unwrap();
+            assert_eq!(file_type.extension(), extension);
+            assert_eq!(file_type.to_string(), directory);
+            assert!(PARSERS.get(file_type).is_some());
+        }
+        assert!(PARSERS.file_type("json").is_none());
+    }
+}
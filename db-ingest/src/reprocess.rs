@@ -0,0 +1,270 @@
+use crate::ingestors::clickhouse_ingestor::ClickhouseIngestor;
+use crate::ingestors::ingestor::MatchSaltsSource;
+use crate::models::error::ParseError;
+use crate::parsers::registry::{ParserRegistry, PARSERS};
+use crate::storage;
+use clap::Parser;
+use ingest_common::file_name::IngestFileName;
+use ingest_common::object_store::{ObjectMeta, ObjectStore, Store};
+use log::{error, info};
+use std::collections::HashSet;
+use std::fs::OpenOptions;
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::Arc;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+/// Runs historical files (e.g. from `/parsed/<type>/` or `/failed/<type>/`) through the
+/// ingestion pipeline again. Source objects are never deleted.
+#[derive(Parser, Debug)]
+#[command(name = "reprocess")]
+pub struct ReprocessArgs {
+    /// S3 prefix to reprocess, e.g. `/parsed/metac/`
+    prefix: String,
+    /// Only list the objects that would be reprocessed
+    #[arg(long)]
+    dry_run: bool,
+    /// Skip files with a lower match id (files without a match id are skipped)
+    #[arg(long)]
+    min_match_id: Option<u64>,
+    /// Skip files with a higher match id (files without a match id are skipped)
+    #[arg(long)]
+    max_match_id: Option<u64>,
+    /// Skip objects last modified before this unix timestamp
+    #[arg(long)]
+    since: Option<i64>,
+    /// Skip objects last modified after this unix timestamp
+    #[arg(long)]
+    until: Option<i64>,
+    /// Successfully reprocessed objects are appended here and skipped when resuming
+    #[arg(long, default_value = "reprocess.checkpoint")]
+    checkpoint: PathBuf,
+    /// Maximum number of files processed at the same time
+    #[arg(long, default_value_t = 50)]
+    concurrency: usize,
+}
+
+impl ReprocessArgs {
+    fn matches(&self, object: &ObjectMeta) -> bool {
+        if self.min_match_id.is_some() || self.max_match_id.is_some() {
+            let match_id = Path::new(&object.key)
+                .file_name()
+                .and_then(|name| IngestFileName::from_str(&name.to_string_lossy()).ok())
+                .and_then(|name| name.match_id());
+            let Some(match_id) = match_id else {
+                return false;
+            };
+            if self.min_match_id.is_some_and(|min| match_id < min)
+                || self.max_match_id.is_some_and(|max| match_id > max)
+            {
+                return false;
+            }
+        }
+        if self.since.is_some() || self.until.is_some() {
+            let Some(last_modified) = object.last_modified else {
+                return false;
+            };
+            let last_modified = last_modified.unix_timestamp();
+            if self.since.is_some_and(|since| last_modified < since)
+                || self.until.is_some_and(|until| last_modified > until)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+pub async fn run(args: ReprocessArgs, store: Arc<Store>) {
+    let ingestor = Arc::new(ClickhouseIngestor::new(args.concurrency));
+    tokio::spawn(ingestor.clone().run_flush_loop());
+    reprocess(args, &PARSERS, ingestor, store).await;
+}
+
+async fn reprocess<I, S>(
+    args: ReprocessArgs,
+    parsers: &'static ParserRegistry<I>,
+    ingestor: Arc<I>,
+    store: Arc<S>,
+) where
+    I: MatchSaltsSource + 'static,
+    S: ObjectStore + 'static,
+{
+    let checkpoint = read_checkpoint(&args.checkpoint);
+    let objects = match storage::list_objects(store.as_ref(), &args.prefix).await {
+        Ok(objects) => objects,
+        Err(e) => {
+            error!("Error listing objects in {}: {}", args.prefix, e);
+            return;
+        }
+    };
+    let objects = objects
+        .into_iter()
+        .filter(|o| !checkpoint.contains(&o.key))
+        .filter(|o| args.matches(o))
+        .collect::<Vec<_>>();
+    info!(
+        "Found {} objects to reprocess ({} already done)",
+        objects.len(),
+        checkpoint.len()
+    );
+    if args.dry_run {
+        for object in objects {
+            info!("Would reprocess: {}", object.key);
+        }
+        return;
+    }
+
+    let mut checkpoint_file = match OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(&args.checkpoint)
+    {
+        Ok(f) => f,
+        Err(e) => {
+            error!("Error opening checkpoint file: {}", e);
+            return;
+        }
+    };
+    let semaphore = Arc::new(Semaphore::new(args.concurrency));
+    let mut tasks = JoinSet::new();
+    let mut processed = 0;
+    let mut failed = 0;
+    let mut on_done = |result: Result<(String, Result<(), ParseError>), _>| match result {
+        Ok((key, Ok(_))) => {
+            processed += 1;
+            if let Err(e) = writeln!(checkpoint_file, "{}", key) {
+                error!("Error writing checkpoint for {}: {}", key, e);
+            }
+        }
+        Ok((key, Err(e))) => {
+            failed += 1;
+            error!("Error reprocessing {}: {}", key, e);
+        }
+        Err(e) => {
+            failed += 1;
+            error!("Reprocess task failed: {:?}", e);
+        }
+    };
+    for object in objects {
+        let permit = semaphore.clone().acquire_owned().await.unwrap();
+        let ingestor = ingestor.clone();
+        let store = store.clone();
+        tasks.spawn(async move {
+            let result =
+                reprocess_object(parsers, ingestor.as_ref(), store.as_ref(), &object.key).await;
+            drop(permit);
+            (object.key, result)
+        });
+        while let Some(result) = tasks.try_join_next() {
+            on_done(result);
+        }
+    }
+    while let Some(result) = tasks.join_next().await {
+        on_done(result);
+    }
+    info!("Reprocessed {} objects, {} failed", processed, failed);
+}
+
+async fn reprocess_object<I: MatchSaltsSource>(
+    parsers: &ParserRegistry<I>,
+    ingestor: &I,
+    store: &impl ObjectStore,
+    key: &str,
+) -> Result<(), ParseError> {
+    info!("Reprocessing file: {}", key);
+    let file_data = parsers.file_data(Path::new(key))?;
+    crate::process_file(parsers, ingestor, store, &file_data, key).await
+}
+
+fn read_checkpoint(path: &Path) -> HashSet<String> {
+    std::fs::File::open(path)
+        .map(BufReader::new)
+        .map(|r| r.lines().map_while(Result::ok).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ingestors::ingestor::RecordingIngestor;
+    use crate::parsers::metadata_content_parser::MetaDataContentParser;
+    use crate::parsers::metadata_parser::MetaDataParser;
+    use ingest_common::object_store::LocalStore;
+
+    const SAMPLE: &str = "T002_M31452_C185_S1764893";
+
+    fn args(prefix: &str, checkpoint: &Path) -> ReprocessArgs {
+        ReprocessArgs::parse_from([
+            "reprocess",
+            prefix,
+            "--checkpoint",
+            checkpoint.to_str().unwrap(),
+        ])
+    }
+
+    #[tokio::test]
+    async fn reprocesses_parsed_files_in_place() {
+        let dir = tempfile::tempdir().unwrap();
+        let checkpoint = dir.path().join("reprocess.checkpoint");
+        let store = Arc::new(LocalStore::new(dir.path().join("store")));
+        let mut parsers = ParserRegistry::default();
+        parsers.register(MetaDataParser);
+        parsers.register(MetaDataContentParser);
+        let parsers = Box::leak(Box::new(parsers));
+        let ingestor = Arc::new(RecordingIngestor::default());
+
+        let meta_path = format!("/parsed/meta/{}.meta", SAMPLE);
+        let metac_path = format!("/parsed/metac/{}.metac.zst", SAMPLE);
+        let sample = std::fs::read(
+            PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+                .join("testdata")
+                .join(format!("{}.meta", SAMPLE)),
+        )
+        .unwrap();
+        storage::upload(store.as_ref(), &sample, &meta_path)
+            .await
+            .unwrap();
+
+        // Keeps the original, which already is in `/parsed/`
+        reprocess(
+            args("/parsed/meta/", &checkpoint),
+            parsers,
+            ingestor.clone(),
+            store.clone(),
+        )
+        .await;
+        let metac = storage::download(store.as_ref(), &metac_path)
+            .await
+            .unwrap();
+        // Stores the unchanged file where it already is
+        reprocess(
+            args("/parsed/metac/", &checkpoint),
+            parsers,
+            ingestor.clone(),
+            store.clone(),
+        )
+        .await;
+
+        assert_eq!(
+            read_checkpoint(&checkpoint),
+            HashSet::from([
+                meta_path.trim_start_matches('/').to_string(),
+                metac_path.trim_start_matches('/').to_string(),
+            ])
+        );
+        assert_eq!(ingestor.dedup_tokens.lock().unwrap().len(), 2);
+        assert_eq!(
+            storage::download(store.as_ref(), &meta_path).await.unwrap(),
+            sample
+        );
+        assert_eq!(
+            storage::download(store.as_ref(), &metac_path)
+                .await
+                .unwrap(),
+            metac
+        );
+    }
+}
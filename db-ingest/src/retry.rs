@@ -0,0 +1,52 @@
+use crate::models::error::ParseError;
+use ingest_common::queue::{Delivery, Headers, Queue};
+use std::sync::LazyLock;
+use std::time::Duration;
+
+pub const ATTEMPT_HEADER: &str = "x-attempt";
+
+pub static MAX_ATTEMPTS: LazyLock<u32> = LazyLock::new(|| {
+    std::env::var("MAX_ATTEMPTS")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(5)
+});
+
+static RETRY_BASE_DELAY_S: LazyLock<u64> = LazyLock::new(|| {
+    std::env::var("RETRY_BASE_DELAY_S")
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .unwrap_or(30)
+});
+
+/// Returns how many times the message has been attempted before, as tracked in its headers.
+pub fn get_attempt(message: &Delivery) -> u32 {
+    message
+        .headers
+        .get(ATTEMPT_HEADER)
+        .copied()
+        .unwrap_or_default()
+}
+
+/// Exponential backoff: the delay doubles with every attempt.
+pub fn get_retry_delay(attempt: u32) -> Duration {
+    Duration::from_secs(*RETRY_BASE_DELAY_S * 2u64.pow(attempt.saturating_sub(1)))
+}
+
+pub fn get_retry_queue(queue: &str, attempt: u32) -> String {
+    format!("{}.retry.{}", queue, attempt)
+}
+
+/// Publishes the message to the delay queue of the attempt, from where it returns to `queue`.
+pub async fn schedule_retry(
+    broker: &impl Queue,
+    queue: &str,
+    body: &[u8],
+    attempt: u32,
+) -> Result<(), ParseError> {
+    let headers = Headers::from([(ATTEMPT_HEADER.to_string(), attempt)]);
+    broker
+        .publish(&get_retry_queue(queue, attempt), body, &headers)
+        .await
+        .map_err(ParseError::Queue)
+}
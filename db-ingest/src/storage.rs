@@ -0,0 +1,58 @@
+use crate::models::error::ParseError;
+use ingest_common::object_store::{ObjectMeta, ObjectReader, ObjectStore};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+pub async fn upload(store: &impl ObjectStore, data: &[u8], path: &str) -> Result<(), ParseError> {
+    upload_stream(store, &mut &*data, path).await
+}
+
+/// Uploads everything read from the reader, using a multipart upload for large files on S3.
+pub async fn upload_stream<R: AsyncRead + Send + Unpin + ?Sized>(
+    store: &impl ObjectStore,
+    reader: &mut R,
+    path: &str,
+) -> Result<(), ParseError> {
+    store.put(path, reader).await.map_err(ParseError::Storage)
+}
+
+/// Streams the object instead of downloading it into memory.
+pub async fn download_stream(
+    store: &impl ObjectStore,
+    path: &str,
+) -> Result<ObjectReader, ParseError> {
+    store.get(path).await.map_err(ParseError::Storage)
+}
+
+pub async fn download(store: &impl ObjectStore, path: &str) -> Result<Vec<u8>, ParseError> {
+    let mut data = vec![];
+    download_stream(store, path)
+        .await?
+        .read_to_end(&mut data)
+        .await
+        .map_err(ParseError::Io)?;
+    Ok(data)
+}
+
+/// Copies an object within the store, without downloading it.
+pub async fn copy(store: &impl ObjectStore, from: &str, to: &str) -> Result<(), ParseError> {
+    store.copy(from, to).await.map_err(ParseError::Storage)
+}
+
+pub async fn delete(store: &impl ObjectStore, path: &str) -> Result<(), ParseError> {
+    store.delete(path).await.map_err(ParseError::Storage)
+}
+
+pub async fn has_file(store: &impl ObjectStore, path: &str) -> Result<bool, ParseError> {
+    store
+        .head(path)
+        .await
+        .map(|meta| meta.is_some())
+        .map_err(ParseError::Storage)
+}
+
+pub async fn list_objects(
+    store: &impl ObjectStore,
+    prefix: &str,
+) -> Result<Vec<ObjectMeta>, ParseError> {
+    store.list(prefix).await.map_err(ParseError::Storage)
+}
@@ -0,0 +1,160 @@
+use crate::dictionaries;
+use crate::models::error::ParseError;
+use crate::models::file_type::FileType;
+use crate::parsers::registry::PARSERS;
+use crate::storage;
+use clap::Parser;
+use ingest_common::object_store::ObjectStore;
+use log::{error, info, warn};
+use std::path::Path;
+
+/// Trains a zstd dictionary from a sample of parsed files and makes it the current dictionary of
+/// the file type. Previous dictionaries are kept, so older files can still be decompressed.
+#[derive(Parser, Debug)]
+#[command(name = "train-dictionary")]
+pub struct TrainDictionaryArgs {
+    /// Extension of the file type to train the dictionary for, e.g. `metac`
+    file_type: String,
+    /// Number of the most recently parsed files used as samples
+    #[arg(long, default_value_t = 1000)]
+    samples: usize,
+    /// Maximum size of the dictionary in bytes
+    #[arg(long, default_value_t = 112_640)]
+    max_size: usize,
+    /// Only train the dictionary and report its compression ratio, without storing it
+    #[arg(long)]
+    dry_run: bool,
+}
+
+pub async fn run(args: TrainDictionaryArgs, store: &impl ObjectStore) {
+    let Some(file_type) = PARSERS.file_type(&args.file_type) else {
+        error!("Unknown file type: {}", args.file_type);
+        return;
+    };
+    let samples = match download_samples(store, file_type, args.samples).await {
+        Ok(samples) if !samples.is_empty() => samples,
+        Ok(_) => {
+            error!("No samples found for {}", file_type);
+            return;
+        }
+        Err(e) => {
+            error!("Error downloading samples: {}", e);
+            return;
+        }
+    };
+    info!("Training dictionary from {} samples", samples.len());
+    let max_size = args.max_size;
+    let trained = tokio::task::spawn_blocking(move || {
+        zstd::dict::from_samples(&samples, max_size).map(|dictionary| (dictionary, samples))
+    })
+    .await;
+    let (dictionary, samples) = match trained {
+        Ok(Ok(trained)) => trained,
+        Ok(Err(e)) => {
+            error!("Error training dictionary: {}", e);
+            return;
+        }
+        Err(e) => {
+            error!("Training task failed: {:?}", e);
+            return;
+        }
+    };
+    let Some(id) = zstd::zstd_safe::get_dict_id_from_dict(&dictionary) else {
+        error!("Trained dictionary has no id");
+        return;
+    };
+    report_ratio(&dictionary, &samples);
+    if args.dry_run {
+        info!("Trained dictionary {} ({} bytes)", id, dictionary.len());
+        return;
+    }
+
+    let dictionary_path = dictionaries::get_dictionary_path(id.get());
+    if let Err(e) = storage::upload(store, &dictionary, &dictionary_path).await {
+        error!("Error uploading dictionary: {}", e);
+        return;
+    }
+    let current_path = dictionaries::get_current_path(file_type);
+    if let Err(e) = storage::upload(store, id.to_string().as_bytes(), &current_path).await {
+        error!("Error updating {}: {}", current_path, e);
+        return;
+    }
+    info!("Dictionary {} is now used for {} files", id, file_type);
+}
+
+async fn download_samples(
+    store: &impl ObjectStore,
+    file_type: FileType,
+    count: usize,
+) -> Result<Vec<Vec<u8>>, ParseError> {
+    let mut objects = storage::list_objects(store, &format!("/parsed/{}/", file_type)).await?;
+    objects.sort_by_key(|o| std::cmp::Reverse(o.last_modified));
+    let mut samples = vec![];
+    for object in objects {
+        if samples.len() >= count {
+            break;
+        }
+        let file_data = match PARSERS.file_data(Path::new(&object.key)) {
+            Ok(file_data) => file_data,
+            Err(e) => {
+                warn!("Skipping sample {}: {}", object.key, e);
+                continue;
+            }
+        };
+        let reader = storage::download_stream(store, &object.key).await?;
+        match file_data
+            .compression
+            .decompress_stream(store, reader, *crate::MAX_DECOMPRESSED_SIZE)
+            .await
+        {
+            Ok(sample) => samples.push(sample),
+            Err(e) => warn!("Skipping sample {}: {}", object.key, e),
+        }
+    }
+    Ok(samples)
+}
+
+fn report_ratio(dictionary: &[u8], samples: &[Vec<u8>]) {
+    let level = zstd::DEFAULT_COMPRESSION_LEVEL;
+    let Ok(mut with_dictionary) = zstd::bulk::Compressor::with_dictionary(level, dictionary) else {
+        return;
+    };
+    let mut without_dictionary = zstd::bulk::Compressor::new(level).ok();
+    let (mut size, mut plain, mut dict) = (0, 0, 0);
+    for sample in samples {
+        size += sample.len();
+        plain += without_dictionary
+            .as_mut()
+            .and_then(|c| c.compress(sample).ok())
+            .map_or(0, |c| c.len());
+        dict += with_dictionary.compress(sample).map_or(0, |c| c.len());
+    }
+    info!(
+        "Samples: {} bytes, zstd: {} bytes, zstd with dictionary: {} bytes",
+        size, plain, dict
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parsers::metadata_parser::METADATA;
+    use ingest_common::object_store::LocalStore;
+
+    #[tokio::test]
+    async fn skips_unparseable_sample_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(dir.path());
+        for (path, data) in [
+            ("/parsed/meta/not-a-match.bin", b"invalid".as_slice()),
+            ("/parsed/meta/T002_M1_C1_S1.meta", b"first".as_slice()),
+            ("/parsed/meta/T002_M2_C1_S1.meta", b"second".as_slice()),
+        ] {
+            storage::upload(&store, data, path).await.unwrap();
+        }
+
+        let mut samples = download_samples(&store, METADATA, 10).await.unwrap();
+        samples.sort();
+        assert_eq!(samples, vec![b"first".to_vec(), b"second".to_vec()]);
+    }
+}
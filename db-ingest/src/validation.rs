@@ -0,0 +1,131 @@
+use crate::ingestors::ingestor::MatchSaltsSource;
+use crate::models::error::ParseError;
+use crate::models::file_data::FileData;
+use crate::models::match_salts::MatchSalts;
+use ingest_common::file_name::{MatchFileStem, TypeCode};
+use log::warn;
+use std::sync::LazyLock;
+
+/// How files are handled whose salts can't be verified, because their match has no `match_salts`
+/// row. Salts are only known for matches that went through user-ingest or the salt scraper.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UnverifiedFiles {
+    /// Ingest the file, counting it in `db_ingest_unverified_files_total`
+    Accept,
+    /// Reject the file as suspicious
+    Reject,
+}
+
+/// Set with `UNVERIFIED_FILES` to `accept` (default) or `reject`.
+pub(crate) static UNVERIFIED_FILES: LazyLock<UnverifiedFiles> =
+    LazyLock::new(|| match std::env::var("UNVERIFIED_FILES").as_deref() {
+        Ok("accept") | Err(_) => UnverifiedFiles::Accept,
+        Ok("reject") => UnverifiedFiles::Reject,
+        Ok(value) => panic!("Invalid UNVERIFIED_FILES: {}", value),
+    });
+
+/// Checks that the file contents belong to the match its name claims, as user-contributed files
+/// are untrusted. Mismatches are reported as [`ParseError::Suspicious`].
+pub async fn validate(
+    salts_source: &impl MatchSaltsSource,
+    file_data: &FileData,
+    match_ids: &[(&'static str, u64)],
+) -> Result<(), ParseError> {
+    let Some(file_name) = file_data.name.match_file() else {
+        return Ok(());
+    };
+    for (field, match_id) in match_ids {
+        if *match_id != file_name.match_id {
+            return Err(ParseError::Suspicious(format!(
+                "{} is {}, but the file name has match id {}",
+                field, match_id, file_name.match_id
+            )));
+        }
+    }
+
+    let salts = salts_source.get_match_salts(file_name.match_id).await?;
+    validate_salts(file_name, salts.as_ref(), *UNVERIFIED_FILES)
+}
+
+fn validate_salts(
+    file_name: &MatchFileStem,
+    salts: Option<&MatchSalts>,
+    unverified_files: UnverifiedFiles,
+) -> Result<(), ParseError> {
+    let Some(salts) = salts else {
+        return match unverified_files {
+            UnverifiedFiles::Accept => {
+                warn!(
+                    "No salts known for match {}, ingesting it unverified",
+                    file_name.match_id
+                );
+                metrics::counter!(
+                    "db_ingest_unverified_files_total",
+                    "type" => format!("{:?}", file_name.type_code)
+                )
+                .increment(1);
+                Ok(())
+            }
+            UnverifiedFiles::Reject => Err(ParseError::Suspicious(format!(
+                "no match_salts row exists for match {}",
+                file_name.match_id
+            ))),
+        };
+    };
+    if salts.cluster_id != file_name.cluster_id {
+        return Err(ParseError::Suspicious(format!(
+            "match_salts has cluster id {}, but the file name has cluster id {}",
+            salts.cluster_id, file_name.cluster_id
+        )));
+    }
+    let salt = match file_name.type_code {
+        TypeCode::Demo => salts.replay_salt,
+        TypeCode::Metadata => salts.metadata_salt,
+    };
+    if salt.is_some_and(|salt| salt != file_name.salt) {
+        return Err(ParseError::Suspicious(format!(
+            "match_salts has salt {:?}, but the file name has salt {}",
+            salt, file_name.salt
+        )));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FILE_NAME: MatchFileStem = MatchFileStem {
+        type_code: TypeCode::Metadata,
+        match_id: 1,
+        cluster_id: 2,
+        salt: 3,
+    };
+
+    fn salts(cluster_id: u32, metadata_salt: Option<u32>) -> MatchSalts {
+        MatchSalts {
+            cluster_id,
+            metadata_salt,
+            replay_salt: None,
+        }
+    }
+
+    #[test]
+    fn missing_salts_follow_the_policy() {
+        assert!(validate_salts(&FILE_NAME, None, UnverifiedFiles::Accept).is_ok());
+        assert!(matches!(
+            validate_salts(&FILE_NAME, None, UnverifiedFiles::Reject),
+            Err(ParseError::Suspicious(_))
+        ));
+    }
+
+    #[test]
+    fn checks_cluster_and_salt() {
+        let policy = UnverifiedFiles::Reject;
+        assert!(validate_salts(&FILE_NAME, Some(&salts(2, Some(3))), policy).is_ok());
+        // The salt of the other file type is not known yet
+        assert!(validate_salts(&FILE_NAME, Some(&salts(2, None)), policy).is_ok());
+        assert!(validate_salts(&FILE_NAME, Some(&salts(4, Some(3))), policy).is_err());
+        assert!(validate_salts(&FILE_NAME, Some(&salts(2, Some(5))), policy).is_err());
+    }
+}
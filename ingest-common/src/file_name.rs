@@ -0,0 +1,448 @@
+//! Grammar of the object names uploaded to `/ingest/`:
+//!
+//! ```text
+//! T<type code>_M<match id>_C<cluster id>_S<salt>.<extension>[.<compression>]   (match files)
+//! <first scraped at>-<last scraped at>.<extension>[.<compression>]           (active matches)
+//! <unix timestamp>.<extension>[.<compression>]                       (legacy active matches)
+//! ```
+//!
+//! Producers build names with [`IngestFileName`]'s `Display` and consumers read them back with
+//! its `FromStr`, so both sides always agree on the format. Only the canonical form is accepted,
+//! e.g. `T2` or `M+5` are rejected, so every name has exactly one spelling.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+#[derive(Debug, Clone, Copy, Eq, Hash, PartialEq)]
+pub enum TypeCode {
+    Demo,
+    Metadata,
+}
+
+impl TypeCode {
+    pub fn code(&self) -> u16 {
+        match self {
+            Self::Demo => 1,
+            Self::Metadata => 2,
+        }
+    }
+
+    /// Extensions a file of this type can have, the first one is used for uploads
+    pub fn extensions(&self) -> &'static [&'static str] {
+        match self {
+            Self::Demo => &["dem"],
+            Self::Metadata => &["meta", "metac"],
+        }
+    }
+}
+
+impl TryFrom<u16> for TypeCode {
+    type Error = FileNameError;
+
+    fn try_from(value: u16) -> Result<Self, Self::Error> {
+        match value {
+            1 => Ok(Self::Demo),
+            2 => Ok(Self::Metadata),
+            _ => Err(FileNameError::UnknownTypeCode(value)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+pub struct MatchFileStem {
+    pub type_code: TypeCode,
+    pub match_id: u64,
+    pub cluster_id: u32,
+    pub salt: u32,
+}
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+pub enum FileStem {
+    Match(MatchFileStem),
+    /// Active matches batch with the snapshots scraped in the window
+    ActiveMatches {
+        first_scraped_at: u64,
+        last_scraped_at: u64,
+    },
+    /// Active matches batch named after its upload time, before the window was encoded
+    LegacyActiveMatches {
+        uploaded_at: u64,
+    },
+}
+
+const ACTIVE_MATCHES_EXTENSION: &str = "amjsonl";
+/// Active matches batches in the delta encoded format of [`crate::snapshot_delta`]
+const ACTIVE_MATCHES_DELTA_EXTENSION: &str = "amdjsonl";
+
+#[derive(Debug, Clone, Eq, Hash, PartialEq)]
+pub struct IngestFileName {
+    pub stem: FileStem,
+    pub extension: String,
+    pub compression: Option<String>,
+}
+
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub enum FileNameError {
+    /// The name has no extension or more than two extensions
+    InvalidExtensions(String),
+    /// A stem part does not start with one of the `T`, `M`, `C` or `S` keywords
+    UnknownKeyword(String),
+    DuplicateKeyword(char),
+    MissingKeyword(char),
+    InvalidNumber {
+        keyword: char,
+        value: String,
+    },
+    InvalidTimestamp(String),
+    UnknownTypeCode(u16),
+    /// The extension is not valid for the type code or stem
+    ExtensionMismatch {
+        stem: String,
+        extension: String,
+    },
+    /// The name parses, but is not spelled the way it is displayed
+    NonCanonical(String),
+}
+
+impl Display for FileNameError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::InvalidExtensions(name) => {
+                write!(
+                    f,
+                    "expected <stem>.<extension>[.<compression>], got {}",
+                    name
+                )
+            }
+            Self::UnknownKeyword(part) => write!(f, "unknown keyword in {}", part),
+            Self::DuplicateKeyword(keyword) => write!(f, "duplicate keyword {}", keyword),
+            Self::MissingKeyword(keyword) => write!(f, "missing keyword {}", keyword),
+            Self::InvalidNumber { keyword, value } => {
+                write!(f, "invalid number for keyword {}: {}", keyword, value)
+            }
+            Self::InvalidTimestamp(value) => write!(f, "invalid timestamp: {}", value),
+            Self::UnknownTypeCode(code) => write!(f, "unknown type code {:03}", code),
+            Self::ExtensionMismatch { stem, extension } => {
+                write!(f, "extension {} does not match {}", extension, stem)
+            }
+            Self::NonCanonical(name) => write!(f, "{} is not in canonical form", name),
+        }
+    }
+}
+
+impl std::error::Error for FileNameError {}
+
+impl IngestFileName {
+    pub fn new(
+        stem: FileStem,
+        extension: &str,
+        compression: Option<&str>,
+    ) -> Result<Self, FileNameError> {
+        let valid_extension = match &stem {
+            FileStem::Match(stem) => stem.type_code.extensions().contains(&extension),
+            FileStem::ActiveMatches { .. } | FileStem::LegacyActiveMatches { .. } => {
+                extension == ACTIVE_MATCHES_EXTENSION || extension == ACTIVE_MATCHES_DELTA_EXTENSION
+            }
+        };
+        if !valid_extension {
+            return Err(FileNameError::ExtensionMismatch {
+                stem: stem.to_string(),
+                extension: extension.to_string(),
+            });
+        }
+        Ok(Self {
+            stem,
+            extension: extension.to_string(),
+            compression: compression.map(str::to_string),
+        })
+    }
+
+    /// Name of a match file as uploaded to `/ingest/`
+    pub fn new_match_file(stem: MatchFileStem, compression: Option<&str>) -> Self {
+        Self {
+            extension: stem.type_code.extensions()[0].to_string(),
+            stem: FileStem::Match(stem),
+            compression: compression.map(str::to_string),
+        }
+    }
+
+    /// Name of an active matches batch as uploaded to `/ingest/`, with the plain or the delta
+    /// encoded extension
+    pub fn new_active_matches(
+        first_scraped_at: u64,
+        last_scraped_at: u64,
+        delta_encoded: bool,
+        compression: Option<&str>,
+    ) -> Self {
+        Self {
+            stem: FileStem::ActiveMatches {
+                first_scraped_at,
+                last_scraped_at,
+            },
+            extension: match delta_encoded {
+                true => ACTIVE_MATCHES_DELTA_EXTENSION,
+                false => ACTIVE_MATCHES_EXTENSION,
+            }
+            .to_string(),
+            compression: compression.map(str::to_string),
+        }
+    }
+
+    pub fn match_file(&self) -> Option<&MatchFileStem> {
+        match &self.stem {
+            FileStem::Match(stem) => Some(stem),
+            FileStem::ActiveMatches { .. } | FileStem::LegacyActiveMatches { .. } => None,
+        }
+    }
+
+    pub fn match_id(&self) -> Option<u64> {
+        self.match_file().map(|m| m.match_id)
+    }
+}
+
+impl Display for MatchFileStem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "T{:03}_M{}_C{}_S{}",
+            self.type_code.code(),
+            self.match_id,
+            self.cluster_id,
+            self.salt
+        )
+    }
+}
+
+impl Display for FileStem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Match(stem) => write!(f, "{}", stem),
+            Self::ActiveMatches {
+                first_scraped_at,
+                last_scraped_at,
+            } => write!(f, "{}-{}", first_scraped_at, last_scraped_at),
+            Self::LegacyActiveMatches { uploaded_at } => write!(f, "{}", uploaded_at),
+        }
+    }
+}
+
+impl Display for IngestFileName {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}.{}", self.stem, self.extension)?;
+        if let Some(compression) = &self.compression {
+            write!(f, ".{}", compression)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for MatchFileStem {
+    type Err = FileNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut type_code = None;
+        let mut match_id = None;
+        let mut cluster_id = None;
+        let mut salt = None;
+        for part in s.split('_') {
+            let mut chars = part.chars();
+            let keyword = chars
+                .next()
+                .ok_or_else(|| FileNameError::UnknownKeyword(part.to_string()))?;
+            let value = chars.as_str();
+            match keyword {
+                'T' => set_keyword(&mut type_code, keyword, value)?,
+                'M' => set_keyword(&mut match_id, keyword, value)?,
+                'C' => set_keyword(&mut cluster_id, keyword, value)?,
+                'S' => set_keyword(&mut salt, keyword, value)?,
+                _ => return Err(FileNameError::UnknownKeyword(part.to_string())),
+            }
+        }
+        let type_code: u16 = type_code.ok_or(FileNameError::MissingKeyword('T'))?;
+        Ok(Self {
+            type_code: TypeCode::try_from(type_code)?,
+            match_id: match_id.ok_or(FileNameError::MissingKeyword('M'))?,
+            cluster_id: cluster_id.ok_or(FileNameError::MissingKeyword('C'))?,
+            salt: salt.ok_or(FileNameError::MissingKeyword('S'))?,
+        })
+    }
+}
+
+fn set_keyword<T: FromStr>(
+    slot: &mut Option<T>,
+    keyword: char,
+    value: &str,
+) -> Result<(), FileNameError> {
+    if slot.is_some() {
+        return Err(FileNameError::DuplicateKeyword(keyword));
+    }
+    let parsed = value.parse().map_err(|_| FileNameError::InvalidNumber {
+        keyword,
+        value: value.to_string(),
+    })?;
+    *slot = Some(parsed);
+    Ok(())
+}
+
+impl FromStr for FileStem {
+    type Err = FileNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let is_timestamp = |t: &str| !t.is_empty() && t.bytes().all(|b| b.is_ascii_digit());
+        let parse_timestamp = |t: &str| {
+            t.parse()
+                .map_err(|_| FileNameError::InvalidTimestamp(s.to_string()))
+        };
+        if let Some((first, last)) = s.split_once('-') {
+            if !is_timestamp(first) || !is_timestamp(last) {
+                return Err(FileNameError::InvalidTimestamp(s.to_string()));
+            }
+            let first_scraped_at = parse_timestamp(first)?;
+            let last_scraped_at = parse_timestamp(last)?;
+            if last_scraped_at < first_scraped_at {
+                return Err(FileNameError::InvalidTimestamp(s.to_string()));
+            }
+            return Ok(Self::ActiveMatches {
+                first_scraped_at,
+                last_scraped_at,
+            });
+        }
+        if is_timestamp(s) {
+            return parse_timestamp(s).map(|uploaded_at| Self::LegacyActiveMatches { uploaded_at });
+        }
+        MatchFileStem::from_str(s).map(Self::Match)
+    }
+}
+
+impl FromStr for IngestFileName {
+    type Err = FileNameError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('.').collect();
+        let (stem, extension, compression) = match parts.as_slice() {
+            [stem, extension, compression] => (stem, extension, Some(*compression)),
+            [stem, extension] => (stem, extension, None),
+            _ => return Err(FileNameError::InvalidExtensions(s.to_string())),
+        };
+        if extension.is_empty() || compression.is_some_and(str::is_empty) {
+            return Err(FileNameError::InvalidExtensions(s.to_string()));
+        }
+        let name = Self::new(FileStem::from_str(stem)?, extension, compression)?;
+        if name.to_string() != s {
+            return Err(FileNameError::NonCanonical(s.to_string()));
+        }
+        Ok(name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(name: &str) -> Result<IngestFileName, FileNameError> {
+        IngestFileName::from_str(name)
+    }
+
+    #[test]
+    fn round_trips() {
+        for name in [
+            "T001_M31452_C185_S1764893.dem.bz2",
+            "T002_M31452_C185_S1764893.meta.bz2",
+            "T002_M31452_C185_S1764893.metac",
+            "1729000000-1729000900.amjsonl.zst",
+            "1729000000-1729000000.amdjsonl.zst",
+            "1729000000.amjsonl.zst",
+        ] {
+            assert_eq!(parse(name).unwrap().to_string(), name);
+        }
+    }
+
+    #[test]
+    fn builds_names() {
+        let stem = MatchFileStem {
+            type_code: TypeCode::Metadata,
+            match_id: 31452,
+            cluster_id: 185,
+            salt: 1764893,
+        };
+        let name = IngestFileName::new_match_file(stem.clone(), Some("bz2"));
+        assert_eq!(name.to_string(), "T002_M31452_C185_S1764893.meta.bz2");
+        assert_eq!(name.match_file(), Some(&stem));
+
+        let name = IngestFileName::new_active_matches(10, 20, false, Some("zst"));
+        assert_eq!(name.to_string(), "10-20.amjsonl.zst");
+        assert_eq!(
+            parse("10-20.amdjsonl").unwrap().stem,
+            FileStem::ActiveMatches {
+                first_scraped_at: 10,
+                last_scraped_at: 20,
+            }
+        );
+    }
+
+    #[test]
+    fn rejects_non_canonical_names() {
+        for name in [
+            "T1_M31452_C185_S1764893.dem",
+            "T001_M+31452_C185_S1764893.dem",
+            "T001_M031452_C185_S1764893.dem",
+            "M31452_T001_C185_S1764893.dem",
+            "+1729000000-1729000900.amjsonl",
+            "01729000000-1729000900.amjsonl",
+            "01729000000.amjsonl",
+        ] {
+            assert!(parse(name).is_err(), "{} should be rejected", name);
+        }
+    }
+
+    #[test]
+    fn rejects_invalid_names() {
+        assert_eq!(
+            parse("T001_M1_C2_S3"),
+            Err(FileNameError::InvalidExtensions(
+                "T001_M1_C2_S3".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("T001_M1_C2_S3.dem.bz2.zst"),
+            Err(FileNameError::InvalidExtensions(
+                "T001_M1_C2_S3.dem.bz2.zst".to_string()
+            ))
+        );
+        assert_eq!(
+            parse("T001_M1_M1_C2_S3.dem"),
+            Err(FileNameError::DuplicateKeyword('M'))
+        );
+        assert_eq!(
+            parse("T001_M1_C2.dem"),
+            Err(FileNameError::MissingKeyword('S'))
+        );
+        assert_eq!(
+            parse("T001_Mx_C2_S3.dem"),
+            Err(FileNameError::InvalidNumber {
+                keyword: 'M',
+                value: "x".to_string(),
+            })
+        );
+        assert_eq!(
+            parse("T003_M1_C2_S3.dem"),
+            Err(FileNameError::UnknownTypeCode(3))
+        );
+        assert!(matches!(
+            parse("T001_M1_C2_S3.meta"),
+            Err(FileNameError::ExtensionMismatch { .. })
+        ));
+        assert!(matches!(
+            parse("10-20.dem"),
+            Err(FileNameError::ExtensionMismatch { .. })
+        ));
+        assert_eq!(
+            parse("20-10.amjsonl"),
+            Err(FileNameError::InvalidTimestamp("20-10".to_string()))
+        );
+        assert_eq!(
+            parse("10-.amjsonl"),
+            Err(FileNameError::InvalidTimestamp("10-".to_string()))
+        );
+    }
+}
@@ -0,0 +1,6 @@
+pub mod file_name;
+pub mod object_store;
+pub mod queue;
+pub mod snapshot_delta;
+#[cfg(feature = "test-util")]
+pub mod testing;
@@ -0,0 +1,361 @@
+use crate::object_store::{ObjectMeta, ObjectReader, ObjectStore, ObjectStoreError};
+use log::debug;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use time::OffsetDateTime;
+use tokio::fs;
+use tokio::io::{self, AsyncRead};
+
+/// Objects are written here first and renamed into place, so readers never see partial objects
+const TEMP_DIR: &str = ".tmp";
+
+static TEMP_COUNTER: AtomicU64 = AtomicU64::new(0);
+
+/// Stores objects as files below a root directory, with the object path as relative file path.
+#[derive(Debug)]
+pub struct LocalStore {
+    root: PathBuf,
+}
+
+impl LocalStore {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// Configured from `OBJECT_STORE_PATH` (default `./objects`).
+    pub fn from_env() -> Self {
+        Self::new(std::env::var("OBJECT_STORE_PATH").unwrap_or("./objects".to_string()))
+    }
+
+    fn file_path(&self, path: &str) -> Result<PathBuf, ObjectStoreError> {
+        let relative = Path::new(path.trim_start_matches('/'));
+        let is_valid = relative
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)))
+            && !relative.starts_with(TEMP_DIR);
+        if !is_valid || relative.as_os_str().is_empty() {
+            return Err(ObjectStoreError::InvalidPath(path.to_string()));
+        }
+        Ok(self.root.join(relative))
+    }
+
+    async fn temp_path(&self) -> Result<PathBuf, ObjectStoreError> {
+        let temp_dir = self.root.join(TEMP_DIR);
+        fs::create_dir_all(&temp_dir)
+            .await
+            .map_err(ObjectStoreError::Io)?;
+        Ok(temp_dir.join(format!(
+            "{}-{}",
+            std::process::id(),
+            TEMP_COUNTER.fetch_add(1, Ordering::Relaxed)
+        )))
+    }
+
+    /// Moves a fully written temporary file to the object path, the temporary file is removed if
+    /// writing it failed.
+    async fn commit(
+        &self,
+        written: Result<(), ObjectStoreError>,
+        temp_path: &Path,
+        file_path: &Path,
+    ) -> Result<(), ObjectStoreError> {
+        let result = match written {
+            Ok(_) => self.rename(temp_path, file_path).await,
+            Err(e) => Err(e),
+        };
+        if result.is_err() {
+            let _ = fs::remove_file(temp_path).await;
+        }
+        result
+    }
+
+    async fn rename(&self, temp_path: &Path, file_path: &Path) -> Result<(), ObjectStoreError> {
+        if let Some(parent) = file_path.parent() {
+            fs::create_dir_all(parent)
+                .await
+                .map_err(ObjectStoreError::Io)?;
+        }
+        fs::rename(temp_path, file_path)
+            .await
+            .map_err(ObjectStoreError::Io)
+    }
+
+    fn meta(&self, file_path: &Path, metadata: &std::fs::Metadata) -> ObjectMeta {
+        let key = file_path
+            .strip_prefix(&self.root)
+            .unwrap_or(file_path)
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect::<Vec<_>>()
+            .join("/");
+        ObjectMeta {
+            key,
+            size: metadata.len(),
+            last_modified: metadata.modified().ok().map(OffsetDateTime::from),
+        }
+    }
+}
+
+fn not_found(path: &str, error: io::Error) -> ObjectStoreError {
+    match error.kind() {
+        ErrorKind::NotFound => ObjectStoreError::NotFound(path.to_string()),
+        _ => ObjectStoreError::Io(error),
+    }
+}
+
+impl ObjectStore for LocalStore {
+    async fn put<R: AsyncRead + Send + Unpin + ?Sized>(
+        &self,
+        path: &str,
+        reader: &mut R,
+    ) -> Result<(), ObjectStoreError> {
+        debug!("Writing to local store: {}", path);
+        let file_path = self.file_path(path)?;
+        let temp_path = self.temp_path().await?;
+        let written = async {
+            let mut file = fs::File::create(&temp_path).await?;
+            io::copy(reader, &mut file).await?;
+            file.sync_all().await
+        }
+        .await
+        .map_err(ObjectStoreError::Io);
+        self.commit(written, &temp_path, &file_path).await
+    }
+
+    async fn get(&self, path: &str) -> Result<ObjectReader, ObjectStoreError> {
+        debug!("Reading from local store: {}", path);
+        let file = fs::File::open(self.file_path(path)?)
+            .await
+            .map_err(|e| not_found(path, e))?;
+        Ok(Box::new(io::BufReader::new(file)))
+    }
+
+    async fn head(&self, path: &str) -> Result<Option<ObjectMeta>, ObjectStoreError> {
+        let file_path = self.file_path(path)?;
+        match fs::metadata(&file_path).await {
+            Ok(metadata) if metadata.is_file() => Ok(Some(self.meta(&file_path, &metadata))),
+            Ok(_) => Ok(None),
+            Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(ObjectStoreError::Io(e)),
+        }
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ObjectStoreError> {
+        debug!("Deleting from local store: {}", path);
+        match fs::remove_file(self.file_path(path)?).await {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(ObjectStoreError::Io(e)),
+            _ => Ok(()),
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, ObjectStoreError> {
+        debug!("Listing objects in local store: {}", prefix);
+        let prefix = prefix.trim_start_matches('/');
+        // Only the directory of the prefix has to be walked, not the whole store
+        let directory = match prefix.rfind('/') {
+            Some(index) => self.file_path(&prefix[..index])?,
+            None => self.root.clone(),
+        };
+        let mut objects = vec![];
+        let mut directories = vec![directory];
+        while let Some(directory) = directories.pop() {
+            let mut entries = match fs::read_dir(&directory).await {
+                Ok(entries) => entries,
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                Err(e) => return Err(ObjectStoreError::Io(e)),
+            };
+            while let Some(entry) = entries.next_entry().await.map_err(ObjectStoreError::Io)? {
+                let entry_path = entry.path();
+                if entry_path == self.root.join(TEMP_DIR) {
+                    continue;
+                }
+                let metadata = entry.metadata().await.map_err(ObjectStoreError::Io)?;
+                if metadata.is_dir() {
+                    directories.push(entry_path);
+                    continue;
+                }
+                let object = self.meta(&entry_path, &metadata);
+                if object.key.starts_with(prefix) {
+                    objects.push(object);
+                }
+            }
+        }
+        objects.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(objects)
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), ObjectStoreError> {
+        debug!("Copying in local store: {} -> {}", from, to);
+        let from_path = self.file_path(from)?;
+        let to_path = self.file_path(to)?;
+        if from_path == to_path {
+            return Err(ObjectStoreError::Status {
+                path: to.to_string(),
+                status_code: 400,
+            });
+        }
+        let temp_path = self.temp_path().await?;
+        let written = fs::copy(&from_path, &temp_path)
+            .await
+            .map(|_| ())
+            .map_err(|e| not_found(from, e));
+        self.commit(written, &temp_path, &to_path).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::io::AsyncReadExt;
+
+    async fn read(store: &LocalStore, path: &str) -> Result<String, ObjectStoreError> {
+        let mut data = String::new();
+        store
+            .get(path)
+            .await?
+            .read_to_string(&mut data)
+            .await
+            .map_err(ObjectStoreError::Io)?;
+        Ok(data)
+    }
+
+    fn keys(objects: Vec<ObjectMeta>) -> Vec<String> {
+        objects.into_iter().map(|o| o.key).collect()
+    }
+
+    fn temp_files(root: &Path) -> usize {
+        std::fs::read_dir(root.join(TEMP_DIR))
+            .map(|entries| entries.count())
+            .unwrap_or_default()
+    }
+
+    #[tokio::test]
+    async fn put_get_copy_delete() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        store
+            .put("/ingest/a.meta", &mut &b"first"[..])
+            .await
+            .unwrap();
+        assert_eq!(read(&store, "/ingest/a.meta").await.unwrap(), "first");
+        store
+            .put("/ingest/a.meta", &mut &b"second"[..])
+            .await
+            .unwrap();
+        assert_eq!(read(&store, "ingest/a.meta").await.unwrap(), "second");
+
+        let meta = store.head("/ingest/a.meta").await.unwrap().unwrap();
+        assert_eq!(meta.key, "ingest/a.meta");
+        assert_eq!(meta.size, 6);
+        assert!(store.head("/ingest/b.meta").await.unwrap().is_none());
+
+        store
+            .copy("/ingest/a.meta", "/parsed/meta/a.meta")
+            .await
+            .unwrap();
+        assert_eq!(read(&store, "/parsed/meta/a.meta").await.unwrap(), "second");
+        // Copying onto itself is rejected, like on S3
+        assert!(matches!(
+            store.copy("/parsed/meta/a.meta", "parsed/meta/a.meta").await,
+            Err(ObjectStoreError::Status {
+                status_code: 400,
+                ..
+            })
+        ));
+
+        store.delete("/ingest/a.meta").await.unwrap();
+        assert!(matches!(
+            read(&store, "/ingest/a.meta").await,
+            Err(ObjectStoreError::NotFound(_))
+        ));
+        // Deleting a missing object is not an error, like on S3
+        store.delete("/ingest/a.meta").await.unwrap();
+        assert_eq!(temp_files(root.path()), 0);
+    }
+
+    #[tokio::test]
+    async fn list_filters_by_prefix() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+        for path in [
+            "/parsed/meta/1.meta",
+            "/parsed/meta/2.meta",
+            "/parsed/metac/1.metac",
+            "/failed/meta/3.meta",
+        ] {
+            store.put(path, &mut &b"data"[..]).await.unwrap();
+        }
+
+        assert_eq!(
+            keys(store.list("/parsed/meta/").await.unwrap()),
+            ["parsed/meta/1.meta", "parsed/meta/2.meta"]
+        );
+        assert_eq!(
+            keys(store.list("/parsed/meta").await.unwrap()),
+            [
+                "parsed/meta/1.meta",
+                "parsed/meta/2.meta",
+                "parsed/metac/1.metac"
+            ]
+        );
+        assert_eq!(
+            keys(store.list("/parsed/meta/2").await.unwrap()),
+            ["parsed/meta/2.meta"]
+        );
+        assert_eq!(store.list("").await.unwrap().len(), 4);
+        assert!(store.list("/missing/").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn rejects_paths_outside_the_store() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path().join("objects"));
+        for path in [
+            "/../a.meta",
+            "/ingest/../../a.meta",
+            "/.tmp/a.meta",
+            ".tmp",
+            "/",
+            "",
+        ] {
+            assert!(
+                matches!(
+                    store.put(path, &mut &b"data"[..]).await,
+                    Err(ObjectStoreError::InvalidPath(_))
+                ),
+                "{} should be rejected",
+                path
+            );
+        }
+        assert!(matches!(
+            store.get("/ingest/../../secret").await,
+            Err(ObjectStoreError::InvalidPath(_))
+        ));
+        assert!(matches!(
+            store.copy("/../a.meta", "/b.meta").await,
+            Err(ObjectStoreError::InvalidPath(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn failed_writes_leave_no_temp_files() {
+        let root = tempfile::tempdir().unwrap();
+        let store = LocalStore::new(root.path());
+
+        let mut failing = tokio_util::io::StreamReader::new(futures_lite::stream::iter([
+            Ok(&b"partial"[..]),
+            Err(io::Error::other("connection reset")),
+        ]));
+        assert!(store.put("/ingest/a.meta", &mut failing).await.is_err());
+        assert!(matches!(
+            store.copy("/ingest/missing.meta", "/parsed/b.meta").await,
+            Err(ObjectStoreError::NotFound(_))
+        ));
+
+        assert!(store.head("/ingest/a.meta").await.unwrap().is_none());
+        assert!(store.head("/parsed/b.meta").await.unwrap().is_none());
+        assert_eq!(temp_files(root.path()), 0);
+    }
+}
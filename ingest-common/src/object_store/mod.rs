@@ -0,0 +1,207 @@
+//! Storage of the ingested files, in S3 or in a local directory.
+//!
+//! The backend is selected with `OBJECT_STORE` (`s3` or `local`). The local backend stores objects
+//! below `OBJECT_STORE_PATH`, so the services can run without MinIO and without credentials.
+
+mod local;
+mod s3;
+
+pub use self::local::LocalStore;
+pub use self::s3::S3Store;
+
+use std::fmt::Display;
+use std::future::Future;
+use std::io;
+use time::OffsetDateTime;
+use tokio::io::{AsyncBufRead, AsyncRead};
+
+pub type ObjectReader = Box<dyn AsyncBufRead + Send + Unpin>;
+
+#[derive(Debug, Clone)]
+pub struct ObjectMeta {
+    /// Path of the object, without a leading slash
+    pub key: String,
+    pub size: u64,
+    pub last_modified: Option<OffsetDateTime>,
+}
+
+#[derive(Debug)]
+pub enum ObjectStoreError {
+    S3(::s3::error::S3Error),
+    Io(io::Error),
+    NotFound(String),
+    /// The backend answered with an unexpected status code
+    Status {
+        path: String,
+        status_code: u16,
+    },
+    /// The path would resolve outside of the store
+    InvalidPath(String),
+    /// An environment variable is missing or has an invalid value
+    InvalidConfig(&'static str),
+}
+
+impl Display for ObjectStoreError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::S3(e) => write!(f, "S3 error: {}", e),
+            Self::Io(e) => write!(f, "IO error: {}", e),
+            Self::NotFound(path) => write!(f, "object not found: {}", path),
+            Self::Status { path, status_code } => {
+                write!(f, "unexpected status {} for {}", status_code, path)
+            }
+            Self::InvalidPath(path) => write!(f, "invalid object path: {}", path),
+            Self::InvalidConfig(name) => write!(f, "missing or invalid {}", name),
+        }
+    }
+}
+
+impl std::error::Error for ObjectStoreError {}
+
+impl ObjectStoreError {
+    /// Errors which might succeed when retried later. Missing objects, invalid requests and
+    /// configuration errors fail the same way again.
+    pub fn is_transient(&self) -> bool {
+        match self {
+            Self::S3(::s3::error::S3Error::HttpFailWithBody(status_code, _)) => {
+                is_transient_status(*status_code)
+            }
+            Self::Status { status_code, .. } => is_transient_status(*status_code),
+            Self::S3(_) | Self::Io(_) => true,
+            Self::NotFound(_) | Self::InvalidPath(_) | Self::InvalidConfig(_) => false,
+        }
+    }
+}
+
+/// Client errors are permanent, except for timeouts and rate limits.
+fn is_transient_status(status_code: u16) -> bool {
+    !(400..500).contains(&status_code) || status_code == 408 || status_code == 429
+}
+
+pub trait ObjectStore: Send + Sync {
+    /// Stores everything read from the reader, replacing an existing object.
+    fn put<R: AsyncRead + Send + Unpin + ?Sized>(
+        &self,
+        path: &str,
+        reader: &mut R,
+    ) -> impl Future<Output = Result<(), ObjectStoreError>> + Send;
+
+    /// Streams the object instead of loading it into memory.
+    fn get(
+        &self,
+        path: &str,
+    ) -> impl Future<Output = Result<ObjectReader, ObjectStoreError>> + Send;
+
+    /// Returns `None` if the object does not exist.
+    fn head(
+        &self,
+        path: &str,
+    ) -> impl Future<Output = Result<Option<ObjectMeta>, ObjectStoreError>> + Send;
+
+    /// Deleting a missing object succeeds.
+    fn delete(&self, path: &str) -> impl Future<Output = Result<(), ObjectStoreError>> + Send;
+
+    /// Lists all objects whose key starts with the prefix.
+    fn list(
+        &self,
+        prefix: &str,
+    ) -> impl Future<Output = Result<Vec<ObjectMeta>, ObjectStoreError>> + Send;
+
+    /// Copies an object within the store, without downloading it. Copying an object onto itself
+    /// fails, as S3 rejects it.
+    fn copy(
+        &self,
+        from: &str,
+        to: &str,
+    ) -> impl Future<Output = Result<(), ObjectStoreError>> + Send;
+}
+
+/// The backend selected through the environment.
+#[derive(Debug)]
+pub enum Store {
+    S3(S3Store),
+    Local(LocalStore),
+}
+
+impl Store {
+    pub fn from_env() -> Result<Self, ObjectStoreError> {
+        match std::env::var("OBJECT_STORE").as_deref() {
+            Ok("local") => Ok(Self::Local(LocalStore::from_env())),
+            Ok("s3") | Err(_) => S3Store::from_env().map(Self::S3),
+            Ok(_) => Err(ObjectStoreError::InvalidConfig("OBJECT_STORE")),
+        }
+    }
+}
+
+impl ObjectStore for Store {
+    async fn put<R: AsyncRead + Send + Unpin + ?Sized>(
+        &self,
+        path: &str,
+        reader: &mut R,
+    ) -> Result<(), ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.put(path, reader).await,
+            Self::Local(store) => store.put(path, reader).await,
+        }
+    }
+
+    async fn get(&self, path: &str) -> Result<ObjectReader, ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.get(path).await,
+            Self::Local(store) => store.get(path).await,
+        }
+    }
+
+    async fn head(&self, path: &str) -> Result<Option<ObjectMeta>, ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.head(path).await,
+            Self::Local(store) => store.head(path).await,
+        }
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.delete(path).await,
+            Self::Local(store) => store.delete(path).await,
+        }
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.list(prefix).await,
+            Self::Local(store) => store.list(prefix).await,
+        }
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), ObjectStoreError> {
+        match self {
+            Self::S3(store) => store.copy(from, to).await,
+            Self::Local(store) => store.copy(from, to).await,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(status_code: u16) -> ObjectStoreError {
+        ObjectStoreError::Status {
+            path: "a".to_string(),
+            status_code,
+        }
+    }
+
+    #[test]
+    fn classifies_transient_errors() {
+        assert!(!ObjectStoreError::NotFound("a".to_string()).is_transient());
+        assert!(!status(403).is_transient());
+        assert!(status(429).is_transient());
+        assert!(status(503).is_transient());
+        assert!(
+            !ObjectStoreError::S3(::s3::error::S3Error::HttpFailWithBody(400, String::new()))
+                .is_transient()
+        );
+        assert!(ObjectStoreError::Io(io::ErrorKind::ConnectionReset.into()).is_transient());
+    }
+}
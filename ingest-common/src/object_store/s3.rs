@@ -0,0 +1,136 @@
+use crate::object_store::{ObjectMeta, ObjectReader, ObjectStore, ObjectStoreError};
+use futures_lite::StreamExt;
+use log::debug;
+use s3::creds::Credentials;
+use s3::error::S3Error;
+use s3::{Bucket, Region};
+use time::format_description::well_known::{Rfc2822, Rfc3339};
+use time::OffsetDateTime;
+use tokio::io::{self, AsyncRead};
+use tokio_util::io::StreamReader;
+
+#[derive(Debug)]
+pub struct S3Store {
+    bucket: Box<Bucket>,
+}
+
+impl S3Store {
+    /// Configured from `S3_BUCKET_NAME` (default `devlock`), `S3_ENDPOINT`, `S3_REGION` and the
+    /// `AWS_ACCESS_KEY_ID` and `AWS_SECRET_ACCESS_KEY` credentials.
+    pub fn from_env() -> Result<Self, ObjectStoreError> {
+        let bucket_name = std::env::var("S3_BUCKET_NAME").unwrap_or("devlock".to_string());
+        let endpoint = std::env::var("S3_ENDPOINT")
+            .map_err(|_| ObjectStoreError::InvalidConfig("S3_ENDPOINT"))?;
+        let region =
+            std::env::var("S3_REGION").map_err(|_| ObjectStoreError::InvalidConfig("S3_REGION"))?;
+        let credentials = Credentials::from_env()
+            .map_err(|_| ObjectStoreError::InvalidConfig("AWS_ACCESS_KEY_ID"))?;
+        let region = Region::Custom { region, endpoint };
+        let bucket =
+            Bucket::new(&bucket_name, region, credentials).map_err(ObjectStoreError::S3)?;
+        Ok(Self { bucket })
+    }
+}
+
+fn is_not_found(error: &S3Error) -> bool {
+    matches!(error, S3Error::HttpFailWithBody(404, _))
+}
+
+fn check_status(path: &str, status_code: u16, expected: u16) -> Result<(), ObjectStoreError> {
+    match status_code {
+        code if code == expected => Ok(()),
+        404 => Err(ObjectStoreError::NotFound(path.to_string())),
+        status_code => Err(ObjectStoreError::Status {
+            path: path.to_string(),
+            status_code,
+        }),
+    }
+}
+
+impl ObjectStore for S3Store {
+    async fn put<R: AsyncRead + Send + Unpin + ?Sized>(
+        &self,
+        path: &str,
+        reader: &mut R,
+    ) -> Result<(), ObjectStoreError> {
+        debug!("Uploading to S3: {}", path);
+        let response = self
+            .bucket
+            .put_object_stream(reader, path)
+            .await
+            .map_err(ObjectStoreError::S3)?;
+        check_status(path, response.status_code(), 200)
+    }
+
+    async fn get(&self, path: &str) -> Result<ObjectReader, ObjectStoreError> {
+        debug!("Streaming from S3: {}", path);
+        let response = match self.bucket.get_object_stream(path).await {
+            Ok(response) => response,
+            Err(e) if is_not_found(&e) => return Err(ObjectStoreError::NotFound(path.to_string())),
+            Err(e) => return Err(ObjectStoreError::S3(e)),
+        };
+        check_status(path, response.status_code, 200)?;
+        Ok(Box::new(StreamReader::new(
+            response.bytes.map(|chunk| chunk.map_err(io::Error::other)),
+        )))
+    }
+
+    async fn head(&self, path: &str) -> Result<Option<ObjectMeta>, ObjectStoreError> {
+        debug!("Checking if file exists in S3: {}", path);
+        let (head, status_code) = match self.bucket.head_object(path).await {
+            Ok(response) => response,
+            Err(e) if is_not_found(&e) => return Ok(None),
+            Err(e) => return Err(ObjectStoreError::S3(e)),
+        };
+        if status_code == 404 {
+            return Ok(None);
+        }
+        check_status(path, status_code, 200)?;
+        Ok(Some(ObjectMeta {
+            key: path.trim_start_matches('/').to_string(),
+            size: head.content_length.unwrap_or_default().max(0) as u64,
+            // HEAD responses use the HTTP date format
+            last_modified: head
+                .last_modified
+                .and_then(|d| OffsetDateTime::parse(&d, &Rfc2822).ok()),
+        }))
+    }
+
+    async fn delete(&self, path: &str) -> Result<(), ObjectStoreError> {
+        debug!("Deleting from S3: {}", path);
+        let response = self
+            .bucket
+            .delete_object(path)
+            .await
+            .map_err(ObjectStoreError::S3)?;
+        check_status(path, response.status_code(), 204)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<ObjectMeta>, ObjectStoreError> {
+        debug!("Listing objects in S3: {}", prefix);
+        let pages = self
+            .bucket
+            .list(prefix.trim_start_matches('/').to_string(), None)
+            .await
+            .map_err(ObjectStoreError::S3)?;
+        Ok(pages
+            .into_iter()
+            .flat_map(|p| p.contents)
+            .map(|o| ObjectMeta {
+                last_modified: OffsetDateTime::parse(&o.last_modified, &Rfc3339).ok(),
+                key: o.key,
+                size: o.size,
+            })
+            .collect())
+    }
+
+    async fn copy(&self, from: &str, to: &str) -> Result<(), ObjectStoreError> {
+        debug!("Copying in S3: {} -> {}", from, to);
+        let status_code = self
+            .bucket
+            .copy_object_internal(from, to)
+            .await
+            .map_err(ObjectStoreError::S3)?;
+        check_status(from, status_code, 200)
+    }
+}
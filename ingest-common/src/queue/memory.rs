@@ -0,0 +1,327 @@
+use crate::queue::{Acker, Delivery, DeliveryStream, Headers, Queue, QueueError};
+use log::debug;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+#[derive(Debug)]
+struct Message {
+    data: Vec<u8>,
+    headers: Headers,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    queues: HashMap<String, VecDeque<Message>>,
+    notifiers: HashMap<String, Arc<Notify>>,
+    /// Target queue and delay of each delay queue
+    delay_queues: HashMap<String, (String, Duration)>,
+    /// Queues bound to each exchange
+    exchanges: HashMap<String, Vec<String>>,
+}
+
+/// Queues living in the memory of the process. Messages are lost when the process exits.
+#[derive(Debug, Default, Clone)]
+pub struct MemoryQueue {
+    state: Arc<Mutex<State>>,
+}
+
+impl MemoryQueue {
+    fn notifier(&self, queue: &str) -> Arc<Notify> {
+        self.state
+            .lock()
+            .unwrap()
+            .notifiers
+            .entry(queue.to_string())
+            .or_default()
+            .clone()
+    }
+
+    fn push(&self, queue: &str, message: Message, front: bool) {
+        let mut state = self.state.lock().unwrap();
+        let messages = state.queues.entry(queue.to_string()).or_default();
+        if front {
+            messages.push_front(message);
+        } else {
+            messages.push_back(message);
+        }
+        state
+            .notifiers
+            .entry(queue.to_string())
+            .or_default()
+            .notify_one();
+    }
+
+    fn pop(&self, queue: &str) -> Option<Message> {
+        self.state
+            .lock()
+            .unwrap()
+            .queues
+            .get_mut(queue)
+            .and_then(VecDeque::pop_front)
+    }
+
+    /// Number of messages waiting in the queue, without the unsettled ones.
+    pub fn len(&self, queue: &str) -> usize {
+        self.state
+            .lock()
+            .unwrap()
+            .queues
+            .get(queue)
+            .map_or(0, VecDeque::len)
+    }
+
+    pub fn is_empty(&self, queue: &str) -> bool {
+        self.len(queue) == 0
+    }
+}
+
+/// Settles a single delivery, requeueing it if it is dropped unsettled like RabbitMQ does when a
+/// consumer disconnects.
+#[derive(Debug)]
+pub struct MemoryAcker {
+    queue: MemoryQueue,
+    name: String,
+    message: Mutex<Option<Message>>,
+}
+
+impl MemoryAcker {
+    fn take(&self) -> Result<Message, QueueError> {
+        self.message
+            .lock()
+            .unwrap()
+            .take()
+            .ok_or(QueueError::AlreadyUsed)
+    }
+
+    pub(crate) fn ack(&self) -> Result<(), QueueError> {
+        self.take().map(|_| ())
+    }
+
+    pub(crate) fn reject(&self, requeue: bool) -> Result<(), QueueError> {
+        let message = self.take()?;
+        if requeue {
+            self.queue.push(&self.name, message, true);
+        }
+        Ok(())
+    }
+}
+
+impl Drop for MemoryAcker {
+    fn drop(&mut self) {
+        if let Some(message) = self.message.get_mut().unwrap().take() {
+            self.queue.push(&self.name, message, true);
+        }
+    }
+}
+
+impl Queue for MemoryQueue {
+    async fn publish(&self, queue: &str, body: &[u8], headers: &Headers) -> Result<(), QueueError> {
+        debug!("Sending message to in-memory queue: {}", queue);
+        let message = Message {
+            data: body.to_vec(),
+            headers: headers.clone(),
+        };
+        let delay_queue = self.state.lock().unwrap().delay_queues.get(queue).cloned();
+        match delay_queue {
+            Some((target_queue, delay)) => {
+                let this = self.clone();
+                tokio::spawn(async move {
+                    tokio::time::sleep(delay).await;
+                    this.push(&target_queue, message, false);
+                });
+            }
+            None => self.push(queue, message, false),
+        }
+        Ok(())
+    }
+
+    async fn publish_to_exchange(
+        &self,
+        exchange: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> Result<(), QueueError> {
+        debug!("Sending message to in-memory exchange: {}", exchange);
+        let queues = self
+            .state
+            .lock()
+            .unwrap()
+            .exchanges
+            .get(exchange)
+            .cloned()
+            .unwrap_or_default();
+        for queue in queues {
+            self.publish(&queue, body, headers).await?;
+        }
+        Ok(())
+    }
+
+    async fn bind_queue(&self, queue: &str, exchange: &str) -> Result<(), QueueError> {
+        let mut state = self.state.lock().unwrap();
+        let queues = state.exchanges.entry(exchange.to_string()).or_default();
+        if !queues.iter().any(|q| q == queue) {
+            queues.push(queue.to_string());
+        }
+        Ok(())
+    }
+
+    async fn consume(&self, queue: &str) -> Result<DeliveryStream, QueueError> {
+        let this = self.clone();
+        let name = queue.to_string();
+        let notifier = self.notifier(queue);
+        Ok(Box::pin(futures_lite::stream::unfold((), move |_| {
+            let this = this.clone();
+            let name = name.clone();
+            let notifier = notifier.clone();
+            async move {
+                loop {
+                    if let Some(message) = this.pop(&name) {
+                        let data = message.data.clone();
+                        let headers = message.headers.clone();
+                        let acker = MemoryAcker {
+                            queue: this.clone(),
+                            name: name.clone(),
+                            message: Mutex::new(Some(message)),
+                        };
+                        let delivery = Delivery {
+                            data,
+                            headers,
+                            acker: Acker::Memory(Arc::new(acker)),
+                        };
+                        return Some((Ok(delivery), ()));
+                    }
+                    notifier.notified().await;
+                }
+            }
+        })))
+    }
+
+    async fn declare_delay_queue(
+        &self,
+        queue: &str,
+        target_queue: &str,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        self.state
+            .lock()
+            .unwrap()
+            .delay_queues
+            .insert(queue.to_string(), (target_queue.to_string(), delay));
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures_lite::StreamExt;
+
+    async fn next(deliveries: &mut DeliveryStream) -> Delivery {
+        tokio::time::timeout(Duration::from_secs(1), deliveries.next())
+            .await
+            .expect("no delivery")
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn acked_messages_are_removed() {
+        let queue = MemoryQueue::default();
+        let headers = Headers::from([("attempt".to_string(), 2)]);
+        queue.publish("q", b"a", &headers).await.unwrap();
+        queue.publish("q", b"b", &Headers::new()).await.unwrap();
+        let mut deliveries = queue.consume("q").await.unwrap();
+
+        let delivery = next(&mut deliveries).await;
+        assert_eq!(delivery.data, b"a");
+        assert_eq!(delivery.headers, headers);
+        delivery.ack().await.unwrap();
+        assert!(matches!(delivery.ack().await, Err(QueueError::AlreadyUsed)));
+        drop(delivery);
+
+        let delivery = next(&mut deliveries).await;
+        assert_eq!(delivery.data, b"b");
+        delivery.ack().await.unwrap();
+        drop(delivery);
+        assert!(queue.is_empty("q"));
+    }
+
+    #[tokio::test]
+    async fn rejected_messages_are_requeued_first() {
+        let queue = MemoryQueue::default();
+        queue.publish("q", b"a", &Headers::new()).await.unwrap();
+        queue.publish("q", b"b", &Headers::new()).await.unwrap();
+        let mut deliveries = queue.consume("q").await.unwrap();
+
+        let delivery = next(&mut deliveries).await;
+        delivery.reject(true).await.unwrap();
+        assert_eq!(queue.len("q"), 2);
+        let delivery = next(&mut deliveries).await;
+        assert_eq!(delivery.data, b"a");
+
+        delivery.reject(false).await.unwrap();
+        drop(delivery);
+        assert_eq!(queue.len("q"), 1);
+        assert_eq!(next(&mut deliveries).await.data, b"b");
+    }
+
+    #[tokio::test]
+    async fn unsettled_messages_are_requeued_on_drop() {
+        let queue = MemoryQueue::default();
+        queue.publish("q", b"a", &Headers::new()).await.unwrap();
+        let mut deliveries = queue.consume("q").await.unwrap();
+
+        let delivery = next(&mut deliveries).await;
+        let acker = delivery.acker.clone();
+        drop(delivery);
+        // A clone of the acker keeps the delivery unsettled
+        assert!(queue.is_empty("q"));
+        drop(acker);
+        assert_eq!(queue.len("q"), 1);
+        assert_eq!(next(&mut deliveries).await.data, b"a");
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn delay_queues_forward_after_the_delay() {
+        let queue = MemoryQueue::default();
+        let delay = Duration::from_secs(60);
+        queue
+            .declare_delay_queue("q.retry", "q", delay)
+            .await
+            .unwrap();
+        queue
+            .publish("q.retry", b"a", &Headers::new())
+            .await
+            .unwrap();
+        assert!(queue.is_empty("q.retry"));
+        assert!(queue.is_empty("q"));
+
+        tokio::time::sleep(delay - Duration::from_secs(1)).await;
+        assert!(queue.is_empty("q"));
+        tokio::time::sleep(Duration::from_secs(2)).await;
+        assert_eq!(queue.len("q"), 1);
+    }
+
+    #[tokio::test]
+    async fn exchanges_fan_out_to_bound_queues() {
+        let queue = MemoryQueue::default();
+        queue
+            .publish_to_exchange("events", b"dropped", &Headers::new())
+            .await
+            .unwrap();
+        queue.bind_queue("a", "events").await.unwrap();
+        queue.bind_queue("b", "events").await.unwrap();
+        queue.bind_queue("b", "events").await.unwrap();
+        queue
+            .publish_to_exchange("events", b"event", &Headers::new())
+            .await
+            .unwrap();
+
+        assert_eq!(queue.len("a"), 1);
+        assert_eq!(queue.len("b"), 1);
+        let mut deliveries = queue.consume("b").await.unwrap();
+        assert_eq!(next(&mut deliveries).await.data, b"event");
+    }
+}
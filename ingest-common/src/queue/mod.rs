@@ -0,0 +1,198 @@
+//! Message queues between the services, in RabbitMQ or in process.
+//!
+//! The backend is selected with `QUEUE_BACKEND` (`rabbitmq` or `memory`). The in-memory backend
+//! has the same ack, reject and requeue semantics, for tests and single-node runs.
+
+mod memory;
+mod rabbitmq;
+
+pub use self::memory::MemoryQueue;
+pub use self::rabbitmq::RabbitMqQueue;
+
+use futures_lite::Stream;
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Numeric message headers, e.g. the retry attempt
+pub type Headers = BTreeMap<String, u32>;
+
+pub type DeliveryStream = Pin<Box<dyn Stream<Item = Result<Delivery, QueueError>> + Send>>;
+
+#[derive(Debug)]
+pub enum QueueError {
+    Rmq(lapin::Error),
+    /// The delivery was already acked or rejected
+    AlreadyUsed,
+    /// An environment variable is missing or has an invalid value
+    InvalidConfig(&'static str),
+}
+
+impl Display for QueueError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Rmq(e) => write!(f, "RabbitMQ error: {}", e),
+            Self::AlreadyUsed => write!(f, "delivery was already acked or rejected"),
+            Self::InvalidConfig(name) => write!(f, "missing or invalid {}", name),
+        }
+    }
+}
+
+impl std::error::Error for QueueError {}
+
+#[derive(Debug)]
+pub struct Delivery {
+    pub data: Vec<u8>,
+    pub headers: Headers,
+    pub acker: Acker,
+}
+
+impl Delivery {
+    pub async fn ack(&self) -> Result<(), QueueError> {
+        self.acker.ack().await
+    }
+
+    pub async fn reject(&self, requeue: bool) -> Result<(), QueueError> {
+        self.acker.reject(requeue).await
+    }
+}
+
+/// Acknowledges a delivery, can be cloned to settle it from another task.
+#[derive(Debug, Clone)]
+pub enum Acker {
+    RabbitMq(lapin::acker::Acker),
+    Memory(Arc<memory::MemoryAcker>),
+}
+
+impl Acker {
+    pub async fn ack(&self) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(acker) => acker
+                .ack(lapin::options::BasicAckOptions::default())
+                .await
+                .map_err(QueueError::Rmq),
+            Self::Memory(acker) => acker.ack(),
+        }
+    }
+
+    /// Rejected messages are put back into the queue if `requeue` is set, otherwise dropped.
+    pub async fn reject(&self, requeue: bool) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(acker) => acker
+                .reject(lapin::options::BasicRejectOptions { requeue })
+                .await
+                .map_err(QueueError::Rmq),
+            Self::Memory(acker) => acker.reject(requeue),
+        }
+    }
+}
+
+pub trait Queue: Send + Sync {
+    fn publish(
+        &self,
+        queue: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Publishes to every queue bound to the fanout exchange, which is declared if it does not
+    /// exist yet. Messages are dropped if no queue is bound.
+    fn publish_to_exchange(
+        &self,
+        exchange: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Declares the queue and the fanout exchange, and binds the queue to it.
+    fn bind_queue(
+        &self,
+        queue: &str,
+        exchange: &str,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+
+    /// Deliveries must be acked or rejected, unsettled ones are requeued once dropped.
+    fn consume(
+        &self,
+        queue: &str,
+    ) -> impl Future<Output = Result<DeliveryStream, QueueError>> + Send;
+
+    /// Declares a queue without consumers, whose messages are moved to `target_queue` after
+    /// `delay`.
+    fn declare_delay_queue(
+        &self,
+        queue: &str,
+        target_queue: &str,
+        delay: Duration,
+    ) -> impl Future<Output = Result<(), QueueError>> + Send;
+}
+
+/// The backend selected through the environment.
+#[derive(Debug)]
+pub enum Broker {
+    RabbitMq(Box<RabbitMqQueue>),
+    Memory(MemoryQueue),
+}
+
+impl Broker {
+    /// The virtual host is only used by RabbitMQ, `%2f` is the default one.
+    pub fn from_env(vhost: &str) -> Result<Self, QueueError> {
+        match std::env::var("QUEUE_BACKEND").as_deref() {
+            Ok("memory") => Ok(Self::Memory(MemoryQueue::default())),
+            Ok("rabbitmq") | Err(_) => {
+                RabbitMqQueue::from_env(vhost).map(|q| Self::RabbitMq(Box::new(q)))
+            }
+            Ok(_) => Err(QueueError::InvalidConfig("QUEUE_BACKEND")),
+        }
+    }
+}
+
+impl Queue for Broker {
+    async fn publish(&self, queue: &str, body: &[u8], headers: &Headers) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(broker) => broker.publish(queue, body, headers).await,
+            Self::Memory(broker) => broker.publish(queue, body, headers).await,
+        }
+    }
+
+    async fn publish_to_exchange(
+        &self,
+        exchange: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(broker) => broker.publish_to_exchange(exchange, body, headers).await,
+            Self::Memory(broker) => broker.publish_to_exchange(exchange, body, headers).await,
+        }
+    }
+
+    async fn bind_queue(&self, queue: &str, exchange: &str) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(broker) => broker.bind_queue(queue, exchange).await,
+            Self::Memory(broker) => broker.bind_queue(queue, exchange).await,
+        }
+    }
+
+    async fn consume(&self, queue: &str) -> Result<DeliveryStream, QueueError> {
+        match self {
+            Self::RabbitMq(broker) => broker.consume(queue).await,
+            Self::Memory(broker) => broker.consume(queue).await,
+        }
+    }
+
+    async fn declare_delay_queue(
+        &self,
+        queue: &str,
+        target_queue: &str,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        match self {
+            Self::RabbitMq(broker) => broker.declare_delay_queue(queue, target_queue, delay).await,
+            Self::Memory(broker) => broker.declare_delay_queue(queue, target_queue, delay).await,
+        }
+    }
+}
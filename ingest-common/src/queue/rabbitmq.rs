@@ -0,0 +1,242 @@
+use crate::queue::{Acker, Delivery, DeliveryStream, Headers, Queue, QueueError};
+use futures_lite::StreamExt;
+use lapin::options::{
+    BasicConsumeOptions, BasicPublishOptions, ExchangeDeclareOptions, QueueBindOptions,
+    QueueDeclareOptions,
+};
+use lapin::types::{AMQPValue, FieldTable, LongString, ShortString};
+use lapin::{BasicProperties, Channel, Connection, ConnectionProperties, ExchangeKind};
+use log::debug;
+use std::time::Duration;
+use tokio::sync::Mutex;
+
+#[derive(Debug, Default)]
+struct State {
+    connection: Option<Connection>,
+    channel: Option<Channel>,
+}
+
+#[derive(Debug)]
+pub struct RabbitMqQueue {
+    url: String,
+    state: Mutex<State>,
+}
+
+impl RabbitMqQueue {
+    pub fn new(url: String) -> Self {
+        Self {
+            url,
+            state: Mutex::new(State::default()),
+        }
+    }
+
+    /// Configured from `RABBITMQ_ADMIN_USER`, `RABBITMQ_ADMIN_PASS`, `RABBITMQ_HOST` and
+    /// `RABBITMQ_PORT`.
+    pub fn from_env(vhost: &str) -> Result<Self, QueueError> {
+        let var = |name| std::env::var(name).map_err(|_| QueueError::InvalidConfig(name));
+        Ok(Self::new(format!(
+            "amqp://{}:{}@{}:{}/{}",
+            var("RABBITMQ_ADMIN_USER")?,
+            var("RABBITMQ_ADMIN_PASS")?,
+            var("RABBITMQ_HOST")?,
+            var("RABBITMQ_PORT")?,
+            vhost
+        )))
+    }
+
+    /// The connection is opened on first use. A closed channel, e.g. after a channel error, is
+    /// replaced by a new one, and a closed connection, e.g. after a broker restart, is reopened.
+    async fn channel(&self) -> Result<Channel, QueueError> {
+        let mut guard = self.state.lock().await;
+        let state = &mut *guard;
+        let connection = state
+            .connection
+            .take()
+            .filter(|connection| connection.status().connected());
+        let connection = match connection {
+            Some(connection) => connection,
+            None => {
+                state.channel = None;
+                debug!("Opening RabbitMQ connection");
+                Connection::connect(&self.url, ConnectionProperties::default())
+                    .await
+                    .map_err(QueueError::Rmq)?
+            }
+        };
+        let connection = state.connection.insert(connection);
+        if let Some(channel) = state
+            .channel
+            .as_ref()
+            .filter(|channel| channel.status().connected())
+        {
+            return Ok(channel.clone());
+        }
+        let channel = connection.create_channel().await.map_err(QueueError::Rmq)?;
+        state.channel = Some(channel.clone());
+        Ok(channel)
+    }
+
+    async fn declare_exchange(&self, exchange: &str) -> Result<(), QueueError> {
+        self.channel()
+            .await?
+            .exchange_declare(
+                exchange,
+                ExchangeKind::Fanout,
+                ExchangeDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await
+            .map_err(QueueError::Rmq)
+    }
+
+    /// Publishes a persistent message.
+    async fn basic_publish(
+        &self,
+        exchange: &str,
+        routing_key: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> Result<(), QueueError> {
+        let mut properties = BasicProperties::default().with_delivery_mode(2);
+        if !headers.is_empty() {
+            let mut field_table = FieldTable::default();
+            for (key, value) in headers {
+                field_table.insert(ShortString::from(key.clone()), AMQPValue::LongUInt(*value));
+            }
+            properties = properties.with_headers(field_table);
+        }
+        self.channel()
+            .await?
+            .basic_publish(
+                exchange,
+                routing_key,
+                BasicPublishOptions::default(),
+                body,
+                properties,
+            )
+            .await
+            .map(|_| ())
+            .map_err(QueueError::Rmq)
+    }
+}
+
+impl From<lapin::message::Delivery> for Delivery {
+    fn from(delivery: lapin::message::Delivery) -> Self {
+        let headers = delivery
+            .properties
+            .headers()
+            .as_ref()
+            .map(|h| {
+                h.inner()
+                    .iter()
+                    .filter_map(|(k, v)| Some((k.to_string(), v.as_long_uint()?)))
+                    .collect()
+            })
+            .unwrap_or_default();
+        Self {
+            data: delivery.data,
+            headers,
+            acker: Acker::RabbitMq(delivery.acker),
+        }
+    }
+}
+
+impl Queue for RabbitMqQueue {
+    async fn publish(&self, queue: &str, body: &[u8], headers: &Headers) -> Result<(), QueueError> {
+        debug!("Sending message to queue: {}", queue);
+        self.basic_publish("", queue, body, headers).await
+    }
+
+    async fn publish_to_exchange(
+        &self,
+        exchange: &str,
+        body: &[u8],
+        headers: &Headers,
+    ) -> Result<(), QueueError> {
+        debug!("Sending message to exchange: {}", exchange);
+        // Publishing to a missing exchange closes the channel, so it is declared every time
+        self.declare_exchange(exchange).await?;
+        self.basic_publish(exchange, "", body, headers).await
+    }
+
+    async fn bind_queue(&self, queue: &str, exchange: &str) -> Result<(), QueueError> {
+        self.declare_exchange(exchange).await?;
+        let channel = self.channel().await?;
+        channel
+            .queue_declare(
+                queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                FieldTable::default(),
+            )
+            .await
+            .map_err(QueueError::Rmq)?;
+        channel
+            .queue_bind(
+                queue,
+                exchange,
+                "",
+                QueueBindOptions::default(),
+                FieldTable::default(),
+            )
+            .await
+            .map_err(QueueError::Rmq)
+    }
+
+    async fn consume(&self, queue: &str) -> Result<DeliveryStream, QueueError> {
+        let consumer = self
+            .channel()
+            .await?
+            .basic_consume(
+                queue,
+                // Let the server generate a unique consumer tag
+                "",
+                BasicConsumeOptions::default(),
+                FieldTable::default(),
+            )
+            .await
+            .map_err(QueueError::Rmq)?;
+        Ok(Box::pin(consumer.map(|delivery| {
+            delivery.map(Delivery::from).map_err(QueueError::Rmq)
+        })))
+    }
+
+    async fn declare_delay_queue(
+        &self,
+        queue: &str,
+        target_queue: &str,
+        delay: Duration,
+    ) -> Result<(), QueueError> {
+        let mut arguments = FieldTable::default();
+        arguments.insert(
+            "x-message-ttl".into(),
+            AMQPValue::LongLongInt(delay.as_millis() as i64),
+        );
+        arguments.insert(
+            "x-dead-letter-exchange".into(),
+            AMQPValue::LongString(LongString::from("")),
+        );
+        arguments.insert(
+            "x-dead-letter-routing-key".into(),
+            AMQPValue::LongString(LongString::from(target_queue)),
+        );
+        self.channel()
+            .await?
+            .queue_declare(
+                queue,
+                QueueDeclareOptions {
+                    durable: true,
+                    ..Default::default()
+                },
+                arguments,
+            )
+            .await
+            .map(|_| ())
+            .map_err(QueueError::Rmq)
+    }
+}
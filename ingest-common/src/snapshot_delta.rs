@@ -0,0 +1,148 @@
+//! Delta encoding of the snapshots of a match, used by the `amdjsonl` active matches format.
+//!
+//! Each line holds one match: the first snapshot with all fields, followed by only the fields
+//! that changed compared to the previous snapshot. Static fields like the player roster are
+//! therefore stored once instead of in every snapshot.
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct DeltaEncoded {
+    /// The first snapshot, empty if there are no snapshots
+    pub header: Map<String, Value>,
+    /// The changed fields of every following snapshot
+    pub deltas: Vec<Map<String, Value>>,
+}
+
+/// A line of an `amdjsonl` batch, holding the snapshots of one match. Batches started before the
+/// scraper switched formats still contain lines with the full snapshots.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum Line<T> {
+    Snapshots(Vec<T>),
+    Delta(DeltaEncoded),
+}
+
+impl<T: DeserializeOwned> Line<T> {
+    pub fn into_snapshots(self) -> serde_json::Result<Vec<T>> {
+        match self {
+            Self::Snapshots(snapshots) => Ok(snapshots),
+            Self::Delta(delta) => decode(delta),
+        }
+    }
+}
+
+/// The snapshots must serialize to JSON objects. Fields may be added by later snapshots, but a
+/// field missing from a snapshot which the previous one had can't be represented and is rejected.
+pub fn encode<T: Serialize>(snapshots: &[T]) -> serde_json::Result<DeltaEncoded> {
+    let mut snapshots = snapshots.iter().map(to_object);
+    let Some(header) = snapshots.next().transpose()? else {
+        return Ok(DeltaEncoded::default());
+    };
+    let mut previous = header.clone();
+    let mut deltas = vec![];
+    for snapshot in snapshots {
+        let snapshot = snapshot?;
+        if let Some(field) = previous.keys().find(|field| !snapshot.contains_key(*field)) {
+            return Err(serde::ser::Error::custom(format!(
+                "field {} is missing from a later snapshot",
+                field
+            )));
+        }
+        let delta = snapshot
+            .iter()
+            .filter(|(field, value)| previous.get(*field) != Some(value))
+            .map(|(field, value)| (field.clone(), value.clone()))
+            .collect();
+        deltas.push(delta);
+        previous = snapshot;
+    }
+    Ok(DeltaEncoded { header, deltas })
+}
+
+pub fn decode<T: DeserializeOwned>(encoded: DeltaEncoded) -> serde_json::Result<Vec<T>> {
+    if encoded.header.is_empty() {
+        return Ok(vec![]);
+    }
+    let mut snapshot = encoded.header;
+    let mut snapshots = vec![serde_json::from_value(Value::Object(snapshot.clone()))?];
+    for delta in encoded.deltas {
+        snapshot.extend(delta);
+        snapshots.push(serde_json::from_value(Value::Object(snapshot.clone()))?);
+    }
+    Ok(snapshots)
+}
+
+fn to_object<T: Serialize>(snapshot: &T) -> serde_json::Result<Map<String, Value>> {
+    match serde_json::to_value(snapshot)? {
+        Value::Object(object) => Ok(object),
+        _ => Err(serde::ser::Error::custom("snapshot is not an object")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Deserialize, Serialize)]
+    struct Snapshot {
+        match_id: u32,
+        duration_s: u32,
+        players: Vec<u64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        winner: Option<u8>,
+    }
+
+    fn snapshot(duration_s: u32, winner: Option<u8>) -> Snapshot {
+        Snapshot {
+            match_id: 1,
+            duration_s,
+            players: vec![10, 11],
+            winner,
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        for snapshots in [
+            vec![],
+            vec![snapshot(0, None)],
+            vec![snapshot(0, None), snapshot(21, None), snapshot(21, Some(1))],
+        ] {
+            let encoded = encode(&snapshots).unwrap();
+            let json = serde_json::to_string(&encoded).unwrap();
+            let decoded: Vec<Snapshot> = decode(serde_json::from_str(&json).unwrap()).unwrap();
+            assert_eq!(decoded, snapshots);
+        }
+    }
+
+    #[test]
+    fn stores_only_changed_fields() {
+        let encoded =
+            encode(&[snapshot(0, None), snapshot(21, None), snapshot(21, Some(1))]).unwrap();
+        assert_eq!(encoded.header.len(), 3);
+        assert_eq!(
+            serde_json::to_value(&encoded.deltas).unwrap(),
+            serde_json::json!([{"duration_s": 21}, {"winner": 1}])
+        );
+    }
+
+    #[test]
+    fn reads_lines_of_both_formats() {
+        let snapshots = vec![snapshot(0, None), snapshot(21, Some(1))];
+        for line in [
+            serde_json::to_string(&snapshots).unwrap(),
+            serde_json::to_string(&encode(&snapshots).unwrap()).unwrap(),
+        ] {
+            let line: Line<Snapshot> = serde_json::from_str(&line).unwrap();
+            assert_eq!(line.into_snapshots().unwrap(), snapshots);
+        }
+    }
+
+    #[test]
+    fn rejects_removed_fields() {
+        assert!(encode(&[snapshot(0, Some(1)), snapshot(21, None)]).is_err());
+    }
+}
@@ -0,0 +1,31 @@
+//! Fixtures shared by the tests of the crates that exchange active matches.
+
+use serde_json::{json, Value};
+
+/// An active match snapshot as the scraper writes it, with one player per team.
+///
+/// The match started at 90, so the duration follows the scrape time.
+pub fn active_match(match_id: u32, scraped_at: u32) -> Value {
+    json!({
+        "match_id": match_id,
+        "scraped_at": scraped_at,
+        "winning_team": 0,
+        "start_time": 90,
+        "players": [
+            {"account_id": 1, "team": 0, "abandoned": false, "hero_id": 6},
+            {"account_id": 2, "team": 1, "abandoned": false, "hero_id": 7},
+        ],
+        "lobby_id": 5,
+        "duration_s": scraped_at.saturating_sub(90),
+        "spectators": 0,
+        "open_spectator_slots": 10,
+        "objectives_mask_team0": 0,
+        "objectives_mask_team1": 1,
+        "net_worth_team_0": 1000,
+        "net_worth_team_1": 900,
+        "match_mode": 1,
+        "game_mode": 1,
+        "match_score": 2000,
+        "region_mode": 0,
+    })
+}
@@ -1,13 +1,14 @@
 use arl::RateLimiter;
 use base64::prelude::*;
 use base64::Engine;
+use futures::StreamExt;
+use ingest_common::queue::{Broker, Queue};
 use log::{debug, info, warn};
 use prost::Message as _;
 use reqwest::{Client, StatusCode};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::cell::LazyCell;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 use std::time::Duration;
 use tokio::time::sleep;
 use valveprotos::deadlock::c_msg_client_to_gc_get_match_meta_data_response::EResult::KEResultRateLimited;
@@ -35,6 +36,10 @@ static CALLS_PER_ACCOUNT_PER_HOUR: LazyLock<usize> = LazyLock::new(|| {
         .expect("CALLS_PER_ACCOUNT_PER_HOUR must be a number")
 });
 
+/// Queue of the events published by the active matches scraper
+const MATCH_EVENTS_QUEUE: &str = "salt_scraper_match_events";
+const MATCH_EVENTS_EXCHANGE: &str = "match_events";
+
 #[derive(Serialize, Deserialize, Debug, Clone)]
 struct InvokeResponse200 {
     data: String,
@@ -49,7 +54,8 @@ async fn main() {
         .timeout(Duration::from_secs(10))
         .build()
         .unwrap();
-    let limiter = RateLimiter::new(NUM_ACCOUNTS * CALLS_PER_ACCOUNT_PER_HOUR / 60, Duration::from_secs(60));
+    let limiter = Arc::new(RateLimiter::new(*NUM_ACCOUNTS * *CALLS_PER_ACCOUNT_PER_HOUR / 60, Duration::from_secs(60)));
+    tokio::spawn(consume_match_events(client.clone(), message_type, limiter.clone()));
     loop {
         let recent_matches = match get_recent_matches(&client).await {
             Ok(matches) => matches,
@@ -188,6 +194,54 @@ async fn ingest_salts(
         .map(|_| ())
 }
 
+#[derive(Deserialize, Debug)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum MatchEvent {
+    MatchFinished { match_id: u64 },
+}
+
+/// Fetches the salts of matches as soon as the active matches scraper sees them finish, the
+/// recent matches are still polled for matches it missed.
+async fn consume_match_events(client: Client, message_type: u32, limiter: Arc<RateLimiter>) {
+    let broker = match Broker::from_env("%2f") {
+        Ok(broker) => broker,
+        Err(e) => {
+            warn!("Not consuming match events, error configuring queue: {}", e);
+            return;
+        }
+    };
+    let events = match broker.bind_queue(MATCH_EVENTS_QUEUE, MATCH_EVENTS_EXCHANGE).await {
+        Ok(_) => broker.consume(MATCH_EVENTS_QUEUE).await,
+        Err(e) => Err(e),
+    };
+    let mut events = match events {
+        Ok(events) => events,
+        Err(e) => {
+            warn!("Not consuming match events: {:?}", e);
+            return;
+        }
+    };
+    while let Some(delivery) = events.next().await {
+        let delivery = match delivery {
+            Ok(delivery) => delivery,
+            Err(e) => {
+                warn!("Error receiving match event: {:?}", e);
+                continue;
+            }
+        };
+        match serde_json::from_slice(&delivery.data) {
+            Ok(MatchEvent::MatchFinished { match_id }) => {
+                fetch_match(&client, message_type, match_id, &limiter).await
+            }
+            Err(e) => warn!("Skipping invalid match event: {:?}", e),
+        }
+        if let Err(e) = delivery.ack().await {
+            warn!("Error acking match event: {:?}", e);
+        }
+    }
+    warn!("Match events consumer stopped");
+}
+
 #[derive(Serialize, Deserialize, Debug)]
 struct RecentMatch {
     match_id: u64,
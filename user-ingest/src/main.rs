@@ -1,4 +1,4 @@
-use crate::download::process_data;
+use crate::download::Downloader;
 use crate::models::DataType;
 use axum::extract::State;
 use axum::http::StatusCode;
@@ -7,20 +7,21 @@ use axum::{Json, Router};
 use clickhouse::Client;
 use futures::future::join_all;
 use futures::FutureExt;
+use ingest_common::object_store::{ObjectStore, Store};
+use ingest_common::queue::{Broker, Headers, Queue};
 use log::{debug, error};
 use models::Salts;
 use serde::Serialize;
 use std::future::IntoFuture;
 use std::net::Ipv4Addr;
-use std::sync::LazyLock;
+use std::sync::{Arc, LazyLock};
 use tokio::io;
 use tokio::net::TcpListener;
 use tokio::sync::mpsc;
 
 mod download;
 mod models;
-mod rmq;
-mod s3;
+mod storage;
 mod utils;
 
 const PROCESS_DEMO: bool = false;
@@ -37,15 +38,23 @@ static CLICKHOUSE_DB: LazyLock<String> = LazyLock::new(|| std::env::var("CLICKHO
 #[derive(Debug, Clone)]
 pub struct AppState {
     salts_channel: mpsc::Sender<Salts>,
+    downloader: Downloader,
 }
 
 #[tokio::main]
 async fn main() -> Result<(), io::Error> {
     env_logger::init();
 
-    let (salts_channel, mut salts_channel_receiver) = mpsc::channel(100);
+    let (salts_channel, salts_channel_receiver) = mpsc::channel(100);
+    let downloader = match Downloader::new(None) {
+        Ok(downloader) => downloader,
+        Err(e) => panic!("Error configuring downloader: {}", e),
+    };
 
-    let state = AppState { salts_channel };
+    let state = AppState {
+        salts_channel,
+        downloader: downloader.clone(),
+    };
 
     let app = Router::new()
         .route("/health", get(health))
@@ -55,45 +64,33 @@ async fn main() -> Result<(), io::Error> {
 
     let listener = TcpListener::bind((Ipv4Addr::UNSPECIFIED, 8080)).await?;
 
-    let downloader = tokio::spawn(async move {
-        let ch_client = Client::default()
-            .with_url(CLICKHOUSE_URL.clone())
-            .with_user(CLICKHOUSE_USER.clone())
-            .with_password(CLICKHOUSE_PASSWORD.clone())
-            .with_database(CLICKHOUSE_DB.clone())
-            .with_compression(clickhouse::Compression::None);
-        while let Some(salts) = salts_channel_receiver.recv().await {
-            match insert_to_clickhouse(&ch_client, &salts).await {
-                Ok(_) => debug!("Inserted salts to clickhouse"),
-                Err(e) => error!("Failed to insert salts to clickhouse: {:?}", e),
-            };
+    let broker = match Broker::from_env("%2f") {
+        Ok(broker) => Arc::new(broker),
+        Err(e) => panic!("Error configuring queue: {}", e),
+    };
+    let public_broker = match Broker::from_env("public") {
+        Ok(broker) => broker,
+        Err(e) => panic!("Error configuring public queue: {}", e),
+    };
+    let store = match Store::from_env() {
+        Ok(store) => Arc::new(store),
+        Err(e) => panic!("Error configuring object store: {}", e),
+    };
 
-            let serialized_salts = serde_json::to_string(&salts);
-            if let Ok(serialized_salts) = serialized_salts {
-                match rmq::add_to_public_queue("matchdata_salts", &serialized_salts).await {
-                    Ok(_) => debug!("Sent salts to queue"),
-                    Err(e) => error!("Failed to send salts to queue: {:?}", e),
-                }
-            } else {
-                error!("Failed to serialize salts: {:?}", serialized_salts);
-            }
-            debug!("Received metadata download task: {:?}", salts);
-            tokio::spawn(async move {
-                debug!("Received metadata download task: {:?}", salts);
-                let demo_result = if PROCESS_DEMO {
-                    process_data(&salts, DataType::Demo).await
-                } else {
-                    Ok(())
-                };
-                let meta_result = process_data(&salts, DataType::Meta).await;
-                let result = demo_result.and(meta_result);
-                match result {
-                    Ok(_) => debug!("Downloaded Match Data"),
-                    Err(e) => error!("Failed to download Match Data: {:?}", e),
-                };
-            });
-        }
-    });
+    let ch_client = Client::default()
+        .with_url(CLICKHOUSE_URL.clone())
+        .with_user(CLICKHOUSE_USER.clone())
+        .with_password(CLICKHOUSE_PASSWORD.clone())
+        .with_database(CLICKHOUSE_DB.clone())
+        .with_compression(clickhouse::Compression::None);
+    let downloader = tokio::spawn(run_downloader(
+        salts_channel_receiver,
+        downloader,
+        ch_client,
+        public_broker,
+        broker,
+        store,
+    ));
 
     let webserver = axum::serve(listener, app)
         .with_graceful_shutdown(utils::shutdown_signal().map(|_| ()))
@@ -104,6 +101,65 @@ async fn main() -> Result<(), io::Error> {
     Ok(())
 }
 
+/// Stores and publishes the received salts, then downloads their files into the store.
+async fn run_downloader<Q, S>(
+    mut salts_channel_receiver: mpsc::Receiver<Salts>,
+    downloader: Downloader,
+    ch_client: Client,
+    public_broker: impl Queue,
+    broker: Arc<Q>,
+    store: Arc<S>,
+) where
+    Q: Queue + 'static,
+    S: ObjectStore + 'static,
+{
+    while let Some(salts) = salts_channel_receiver.recv().await {
+        match insert_to_clickhouse(&ch_client, &salts).await {
+            Ok(_) => debug!("Inserted salts to clickhouse"),
+            Err(e) => error!("Failed to insert salts to clickhouse: {:?}", e),
+        };
+
+        let serialized_salts = serde_json::to_string(&salts);
+        if let Ok(serialized_salts) = serialized_salts {
+            match public_broker
+                .publish(
+                    "matchdata_salts",
+                    serialized_salts.as_bytes(),
+                    &Headers::new(),
+                )
+                .await
+            {
+                Ok(_) => debug!("Sent salts to queue"),
+                Err(e) => error!("Failed to send salts to queue: {:?}", e),
+            }
+        } else {
+            error!("Failed to serialize salts: {:?}", serialized_salts);
+        }
+        debug!("Received metadata download task: {:?}", salts);
+        let downloader = downloader.clone();
+        let broker = broker.clone();
+        let store = store.clone();
+        tokio::spawn(async move {
+            debug!("Received metadata download task: {:?}", salts);
+            let demo_result = if PROCESS_DEMO {
+                downloader
+                    .process_data(&salts, DataType::Demo, store.as_ref(), broker.as_ref())
+                    .await
+            } else {
+                Ok(())
+            };
+            let meta_result = downloader
+                .process_data(&salts, DataType::Meta, store.as_ref(), broker.as_ref())
+                .await;
+            let result = demo_result.and(meta_result);
+            match result {
+                Ok(_) => debug!("Downloaded Match Data"),
+                Err(e) => error!("Failed to download Match Data: {:?}", e),
+            };
+        });
+    }
+}
+
 async fn insert_to_clickhouse(ch_client: &Client, salts: &Salts) -> clickhouse::error::Result<()> {
     let mut insert = ch_client.insert("match_salts")?;
     insert.write(salts).await?;
@@ -126,16 +182,108 @@ pub async fn post_salts(
 ) -> Result<(), StatusCode> {
     debug!("Received Salts: {:?}", salts);
     for salt in salts {
-        let salts = match download::check_salts(salt.clone()).await {
-            Ok(salts) => salts,
-            Err(e) => {
-                error!("Failed to validate salts: {:?}", e);
-                return Err(StatusCode::INTERNAL_SERVER_ERROR);
-            }
-        };
+        let salts = state.downloader.check_salts(salt).await;
         if state.salts_channel.send(salts.clone()).await.is_err() {
             return Err(StatusCode::INTERNAL_SERVER_ERROR);
         }
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::extract::Path;
+    use clickhouse::test::{handlers, Mock};
+    use futures::StreamExt;
+    use ingest_common::object_store::LocalStore;
+    use ingest_common::queue::MemoryQueue;
+    use std::time::Duration;
+    use tokio::io::AsyncReadExt;
+
+    const METADATA: &[u8] = b"metadata";
+
+    /// Serves the metadata of match 31452 with salt 1764893, like a replay server.
+    async fn replay_server() -> String {
+        let app = Router::new().route(
+            "/1422450/:file",
+            get(|Path(file): Path<String>| async move {
+                match file.as_str() {
+                    "31452_1764893.meta.bz2" => Ok(METADATA),
+                    _ => Err(StatusCode::NOT_FOUND),
+                }
+            }),
+        );
+        let listener = TcpListener::bind((Ipv4Addr::LOCALHOST, 0)).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(axum::serve(listener, app).into_future());
+        url
+    }
+
+    #[tokio::test]
+    async fn downloads_the_files_of_posted_salts() {
+        let mock = Mock::new();
+        let inserted = mock.add(handlers::record::<Salts>());
+        let dir = tempfile::tempdir().unwrap();
+        let store = Arc::new(LocalStore::new(dir.path()));
+        let broker = Arc::new(MemoryQueue::default());
+        let public_broker = MemoryQueue::default();
+        let downloader = Downloader::new(Some(replay_server().await)).unwrap();
+        let (salts_channel, salts_channel_receiver) = mpsc::channel(1);
+        tokio::spawn(run_downloader(
+            salts_channel_receiver,
+            downloader.clone(),
+            Client::default().with_url(mock.url()),
+            public_broker.clone(),
+            broker.clone(),
+            store.clone(),
+        ));
+        let state = AppState {
+            salts_channel,
+            downloader,
+        };
+
+        // The replay salt is unknown to the replay server
+        let salts = Salts {
+            cluster_id: 185,
+            match_id: 31452,
+            metadata_salt: Some(1764893),
+            replay_salt: Some(1),
+        };
+        post_salts(State(state), Json(vec![salts])).await.unwrap();
+
+        let mut ingest_deliveries = broker.consume("db_ingest_queue").await.unwrap();
+        let delivery = tokio::time::timeout(Duration::from_secs(10), ingest_deliveries.next())
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        let object_path = String::from_utf8(delivery.data.clone()).unwrap();
+        assert_eq!(
+            object_path,
+            "/ingest/user-ingest/meta/T002_M31452_C185_S1764893.meta.bz2"
+        );
+        delivery.ack().await.unwrap();
+        let mut data = vec![];
+        store
+            .get(&object_path)
+            .await
+            .unwrap()
+            .read_to_end(&mut data)
+            .await
+            .unwrap();
+        assert_eq!(data, METADATA);
+
+        let mut public_deliveries = public_broker.consume("matchdata_salts").await.unwrap();
+        let delivery = public_deliveries.next().await.unwrap().unwrap();
+        let published: Salts = serde_json::from_slice(&delivery.data).unwrap();
+        assert_eq!(published.metadata_salt, Some(1764893));
+        assert_eq!(published.replay_salt, None);
+        delivery.ack().await.unwrap();
+
+        let inserted: Vec<Salts> = inserted.collect().await;
+        assert_eq!(inserted.len(), 1);
+        assert_eq!(inserted[0].match_id, 31452);
+        assert_eq!(inserted[0].replay_salt, None);
+    }
+}
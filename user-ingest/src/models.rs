@@ -1,4 +1,6 @@
 use clickhouse::Row;
+use ingest_common::object_store::ObjectStoreError;
+use ingest_common::queue::QueueError;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
 use std::io;
@@ -6,9 +8,9 @@ use std::io;
 #[derive(Debug)]
 pub enum ProcessError {
     Reqwest(reqwest::Error),
-    S3(s3::error::S3Error),
+    Storage(ObjectStoreError),
     Io(io::Error),
-    RmqError(lapin::Error),
+    Queue(QueueError),
 }
 
 #[derive(Serialize, Deserialize, Debug, Clone, Row)]
@@ -0,0 +1,28 @@
+use crate::models::ProcessError;
+
+use ingest_common::object_store::ObjectStore;
+use std::path::PathBuf;
+use tokio::io;
+
+pub async fn upload_file(
+    store: &impl ObjectStore,
+    local_path: &PathBuf,
+    path: &str,
+) -> Result<(), ProcessError> {
+    let file = tokio::fs::File::open(local_path)
+        .await
+        .map_err(ProcessError::Io)?;
+    let mut stream = io::BufReader::new(file);
+    store
+        .put(path, &mut stream)
+        .await
+        .map_err(ProcessError::Storage)
+}
+
+pub async fn has_file(store: &impl ObjectStore, path: &str) -> Result<bool, ProcessError> {
+    store
+        .head(path)
+        .await
+        .map(|meta| meta.is_some())
+        .map_err(ProcessError::Storage)
+}